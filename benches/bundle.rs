@@ -0,0 +1,74 @@
+//! Time collecting and writing a bundle of a synthetic source tree at several `jobs` settings
+//!
+//! Run with `cargo bench`; `DIRCAT_BENCH_FILES` sets the tree size (default 5000).
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use dircat::{Bundler, Format};
+
+/// Write `count` Rust-like files, 100 per directory, under a fresh temporary directory
+fn make_tree(count: usize) -> PathBuf {
+    let root = std::env::temp_dir().join(format!("dircat-bench-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    let body: String = (0..60)
+        .map(|i| {
+            format!(
+                "pub fn function_{}(x: u64) -> u64 {{\n    x.wrapping_mul({}) ^ 0x5f\n}}\n\n",
+                i, i
+            )
+        })
+        .collect();
+    for i in 0..count {
+        let dir = root.join(format!("src/module_{:03}", i / 100));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join(format!("file_{}.rs", i)),
+            format!("// file {}\n{}", i, body),
+        )
+        .unwrap();
+    }
+    root
+}
+
+fn bundle(root: &Path, jobs: usize, format: Format) {
+    let bundle = Bundler::new(root)
+        .include(["*.rs"])
+        .format(format)
+        .jobs(jobs)
+        .collect()
+        .unwrap();
+    bundle.write(&mut io::sink()).unwrap();
+}
+
+fn bench_jobs(c: &mut Criterion) {
+    let count = std::env::var("DIRCAT_BENCH_FILES")
+        .ok()
+        .and_then(|n| n.parse().ok())
+        .unwrap_or(5000);
+    let root = make_tree(count);
+    let cpus = std::thread::available_parallelism().map_or(1, |n| n.get());
+
+    let mut jobs = vec![1, 2, 4, 8, cpus];
+    jobs.sort_unstable();
+    jobs.dedup();
+
+    for (name, format) in [("markdown", Format::Markdown), ("jsonl", Format::JsonLines)] {
+        let mut group = c.benchmark_group(format!("pack/{}", name));
+        group.sample_size(10);
+        group.throughput(Throughput::Elements(count as u64));
+        for &j in &jobs {
+            group.bench_with_input(BenchmarkId::new("jobs", j), &j, |b, &j| {
+                b.iter(|| bundle(&root, j, format));
+            });
+        }
+        group.finish();
+    }
+
+    let _ = fs::remove_dir_all(&root);
+}
+
+criterion_group!(benches, bench_jobs);
+criterion_main!(benches);
@@ -0,0 +1,667 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::unpack::{check_resolved, is_closing_fence, opening_fence, safe_join};
+
+/// One line of a unified diff hunk
+enum HunkLine {
+    Context(String),
+    Delete(String),
+    Insert(String),
+}
+
+/// A unified diff hunk; the line counts in the header are ignored since
+/// hand-written and LLM-written diffs often get them wrong
+struct Hunk {
+    header: String,
+    old_start: usize,
+    lines: Vec<HunkLine>,
+}
+
+/// A single change requested by the response file
+enum Edit {
+    /// Replace (or create) the whole file
+    Replace { content: String },
+    /// Apply unified diff hunks; `delete` when the diff targets /dev/null
+    Patch { hunks: Vec<Hunk>, delete: bool },
+    /// Replace the first occurrence of `search` with `replace`
+    SearchReplace { search: String, replace: String },
+}
+
+const SEARCH_MARKER: &str = "<<<<<<< SEARCH";
+const DIVIDER_MARKER: &str = "=======";
+const REPLACE_MARKER: &str = ">>>>>>> REPLACE";
+
+/// Clean up a path as it appears in a heading or a line before a block
+fn clean_path(line: &str) -> Option<String> {
+    let path = line
+        .trim()
+        .trim_start_matches('#')
+        .trim()
+        .trim_matches('`')
+        .trim_matches('*')
+        .trim();
+    if path.is_empty() || path.contains(char::is_whitespace) {
+        None
+    } else {
+        Some(path.to_string())
+    }
+}
+
+/// Path from a `--- a/path` or `+++ b/path` header line
+fn diff_header_path(line: &str, prefix: &str) -> Option<String> {
+    let rest = line[4..].split('\t').next()?.trim();
+    if rest == "/dev/null" {
+        return None;
+    }
+    let rest = rest.trim_matches('"');
+    Some(rest.strip_prefix(prefix).unwrap_or(rest).to_string())
+}
+
+/// Parse the `@@ -a,b +c,d @@` header, returning the old start line
+fn hunk_old_start(line: &str) -> usize {
+    line.trim_start_matches('@')
+        .split_whitespace()
+        .next()
+        .and_then(|r| r.strip_prefix('-'))
+        .and_then(|r| r.split(',').next())
+        .and_then(|n| n.parse().ok())
+        .unwrap_or(0)
+}
+
+/// Whether `lines[i]` starts a `--- ` / `+++ ` file header pair
+fn is_diff_header(lines: &[&str], i: usize) -> bool {
+    lines[i].starts_with("--- ") && lines.get(i + 1).is_some_and(|l| l.starts_with("+++ "))
+}
+
+/// Parse a unified diff starting at a `--- ` header; returns the edits and the
+/// index of the first line after the diff
+fn parse_unified_diff(lines: &[&str], start: usize) -> (Vec<(String, Edit)>, usize) {
+    let mut edits = Vec::new();
+    let mut i = start;
+
+    while i < lines.len() && is_diff_header(lines, i) {
+        let old_path = diff_header_path(lines[i], "a/");
+        let new_path = diff_header_path(lines[i + 1], "b/");
+        i += 2;
+
+        let mut hunks: Vec<Hunk> = Vec::new();
+        while i < lines.len() && !is_diff_header(lines, i) {
+            let line = lines[i];
+            if line.starts_with("@@") {
+                hunks.push(Hunk {
+                    header: line.to_string(),
+                    old_start: hunk_old_start(line),
+                    lines: Vec::new(),
+                });
+            } else if let Some(hunk) = hunks.last_mut() {
+                let parsed = if let Some(rest) = line.strip_prefix('+') {
+                    HunkLine::Insert(rest.to_string())
+                } else if let Some(rest) = line.strip_prefix('-') {
+                    HunkLine::Delete(rest.to_string())
+                } else if let Some(rest) = line.strip_prefix(' ') {
+                    HunkLine::Context(rest.to_string())
+                } else if line.is_empty() {
+                    HunkLine::Context(String::new())
+                } else if line.starts_with('\\') {
+                    i += 1;
+                    continue;
+                } else {
+                    break;
+                };
+                hunk.lines.push(parsed);
+            } else if !line.starts_with("diff ") && !line.starts_with("index ") {
+                break;
+            }
+            i += 1;
+        }
+
+        // Trailing blank lines belong to the surrounding text, not the last hunk
+        if let Some(hunk) = hunks.last_mut() {
+            while matches!(hunk.lines.last(), Some(HunkLine::Context(l)) if l.is_empty()) {
+                hunk.lines.pop();
+            }
+        }
+
+        let delete = new_path.is_none();
+        if let Some(path) = new_path.or(old_path) {
+            edits.push((path, Edit::Patch { hunks, delete }));
+        }
+
+        // Skip `diff --git` / `index` lines between files
+        while i < lines.len() && (lines[i].starts_with("diff ") || lines[i].starts_with("index ")) {
+            i += 1;
+        }
+    }
+
+    (edits, i)
+}
+
+/// Parse SEARCH/REPLACE blocks in `lines`, using the closest preceding
+/// path-like line (or `default_path`) as the target file
+fn parse_search_replace(lines: &[&str], default_path: Option<&str>) -> Vec<(String, Edit)> {
+    let mut edits = Vec::new();
+    let mut path = default_path.map(str::to_string);
+    let mut i = 0;
+
+    while i < lines.len() {
+        let line = lines[i];
+        if line.trim_end() != SEARCH_MARKER {
+            if let Some(p) = clean_path(line) {
+                path = Some(p);
+            }
+            i += 1;
+            continue;
+        }
+
+        let mut search = String::new();
+        let mut replace = String::new();
+        let mut in_replace = false;
+        i += 1;
+        while i < lines.len() && lines[i].trim_end() != REPLACE_MARKER {
+            if !in_replace && lines[i].trim_end() == DIVIDER_MARKER {
+                in_replace = true;
+            } else if in_replace {
+                replace.push_str(lines[i]);
+                replace.push('\n');
+            } else {
+                search.push_str(lines[i]);
+                search.push('\n');
+            }
+            i += 1;
+        }
+        i += 1;
+
+        match &path {
+            Some(p) => edits.push((p.clone(), Edit::SearchReplace { search, replace })),
+            None => eprintln!("Skipping SEARCH/REPLACE block with no file path"),
+        }
+    }
+
+    edits
+}
+
+/// Parse a response into edits, in the order they appear
+///
+/// Understands unified diffs (fenced or bare), `### path` headings followed by a
+/// code block holding the whole new file, and SEARCH/REPLACE blocks preceded by
+/// the file path.
+fn parse_response(text: &str) -> Vec<(String, Edit)> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut edits = Vec::new();
+    let mut heading_path: Option<String> = None;
+    let mut previous_line: Option<&str> = None;
+    let mut i = 0;
+
+    while i < lines.len() {
+        let line = lines[i];
+
+        if let Some(heading) = line.strip_prefix("### ") {
+            heading_path = clean_path(heading);
+            previous_line = Some(line);
+            i += 1;
+            continue;
+        }
+
+        if is_diff_header(&lines, i) {
+            let (diff_edits, next) = parse_unified_diff(&lines, i);
+            edits.extend(diff_edits);
+            i = next;
+            continue;
+        }
+
+        if line.trim_end() == SEARCH_MARKER {
+            let start = i;
+            while i < lines.len() && lines[i].trim_end() != REPLACE_MARKER {
+                i += 1;
+            }
+            let default = previous_line.and_then(clean_path).or(heading_path.clone());
+            edits.extend(parse_search_replace(
+                &lines[start..(i + 1).min(lines.len())],
+                default.as_deref(),
+            ));
+            i += 1;
+            continue;
+        }
+
+        if let Some((fence_char, fence_len)) = opening_fence(line) {
+            let start = i + 1;
+            let mut end = start;
+            while end < lines.len() && !is_closing_fence(lines[end], fence_char, fence_len) {
+                end += 1;
+            }
+            let body = &lines[start..end];
+
+            if body.iter().any(|l| l.trim_end() == SEARCH_MARKER) {
+                let default = previous_line.and_then(clean_path).or(heading_path.take());
+                edits.extend(parse_search_replace(body, default.as_deref()));
+            } else if let Some(diff_start) = (0..body.len()).find(|&j| is_diff_header(body, j)) {
+                edits.extend(parse_unified_diff(body, diff_start).0);
+            } else if let Some(path) = heading_path.take() {
+                let mut content = String::new();
+                for l in body {
+                    content.push_str(l);
+                    content.push('\n');
+                }
+                edits.push((path, Edit::Replace { content }));
+            }
+
+            previous_line = None;
+            i = end + 1;
+            continue;
+        }
+
+        if !line.trim().is_empty() {
+            previous_line = Some(line);
+        }
+        i += 1;
+    }
+
+    edits
+}
+
+/// Find `needle` in `haystack` starting at any line, preferring the match
+/// closest to `hint`; falls back to ignoring trailing whitespace
+///
+/// A trailing `\r` never counts, so a diff made on a CRLF file applies to it
+/// whichever line endings the response was saved with.
+fn find_lines(haystack: &[String], needle: &[&str], hint: usize) -> Option<usize> {
+    if needle.is_empty() {
+        return Some(hint.min(haystack.len()));
+    }
+    if needle.len() > haystack.len() {
+        return None;
+    }
+
+    let candidates = 0..=haystack.len() - needle.len();
+    let exact = |at: usize| {
+        needle
+            .iter()
+            .enumerate()
+            .all(|(k, l)| haystack[at + k].trim_end_matches('\r') == l.trim_end_matches('\r'))
+    };
+    let loose = |at: usize| {
+        needle
+            .iter()
+            .enumerate()
+            .all(|(k, l)| haystack[at + k].trim_end() == l.trim_end())
+    };
+
+    let closest = |matches: &dyn Fn(usize) -> bool| {
+        candidates
+            .clone()
+            .filter(|&at| matches(at))
+            .min_by_key(|&at| at.abs_diff(hint))
+    };
+    closest(&exact).or_else(|| closest(&loose))
+}
+
+/// A file split into lines without their endings, and what it takes to put
+/// it back together
+struct Lines {
+    lines: Vec<String>,
+    /// `\r\n` if the first line ends that way, so CRLF files stay CRLF
+    ending: &'static str,
+    trailing_newline: bool,
+}
+
+fn split_lines(text: &str) -> Lines {
+    let crlf = text.find('\n').is_some_and(|i| text[..i].ends_with('\r'));
+    Lines {
+        lines: text.lines().map(str::to_string).collect(),
+        ending: if crlf { "\r\n" } else { "\n" },
+        trailing_newline: text.is_empty() || text.ends_with('\n'),
+    }
+}
+
+fn join_lines(split: &Lines) -> String {
+    let mut out = split.lines.join(split.ending);
+    if split.trailing_newline && !split.lines.is_empty() {
+        out.push_str(split.ending);
+    }
+    out
+}
+
+/// Apply one edit to the in-memory file; `None` content means the file doesn't exist.
+/// Returns a description of every part that failed to apply.
+fn apply_edit(content: &mut Option<String>, edit: &Edit) -> Vec<String> {
+    let mut failures = Vec::new();
+
+    match edit {
+        Edit::Replace { content: new } => *content = Some(new.clone()),
+        Edit::Patch { hunks, delete } => {
+            let mut split = split_lines(content.as_deref().unwrap_or(""));
+            let lines = &mut split.lines;
+            let mut offset: isize = 0;
+
+            for (n, hunk) in hunks.iter().enumerate() {
+                let old: Vec<&str> = hunk
+                    .lines
+                    .iter()
+                    .filter_map(|l| match l {
+                        HunkLine::Context(s) | HunkLine::Delete(s) => Some(s.as_str()),
+                        HunkLine::Insert(_) => None,
+                    })
+                    .collect();
+                let new: Vec<String> = hunk
+                    .lines
+                    .iter()
+                    .filter_map(|l| match l {
+                        HunkLine::Context(s) | HunkLine::Insert(s) => Some(s.clone()),
+                        HunkLine::Delete(_) => None,
+                    })
+                    .collect();
+
+                let hint = (hunk.old_start.saturating_sub(1) as isize + offset).max(0) as usize;
+                match find_lines(lines, &old, hint) {
+                    Some(at) => {
+                        offset += new.len() as isize - old.len() as isize;
+                        lines.splice(at..at + old.len(), new);
+                    }
+                    None => {
+                        failures.push(format!("hunk {} ({}) did not match", n + 1, hunk.header))
+                    }
+                }
+            }
+
+            if *delete && failures.is_empty() {
+                *content = None;
+            } else {
+                *content = Some(join_lines(&split));
+            }
+        }
+        Edit::SearchReplace { search, replace } => match content {
+            None if search.trim().is_empty() => *content = Some(replace.clone()),
+            None => failures.push("SEARCH block targets a file that doesn't exist".to_string()),
+            Some(_) if search.trim().is_empty() => {
+                failures.push("empty SEARCH block for an existing file".to_string())
+            }
+            Some(text) => {
+                if let Some(at) = text.find(search.as_str()) {
+                    text.replace_range(at..at + search.len(), replace);
+                } else {
+                    let mut split = split_lines(text);
+                    let needle: Vec<&str> = search.lines().collect();
+                    match find_lines(&split.lines, &needle, 0) {
+                        Some(at) => {
+                            let new: Vec<String> = replace.lines().map(str::to_string).collect();
+                            split.lines.splice(at..at + needle.len(), new);
+                            *text = join_lines(&split);
+                        }
+                        None => failures.push(format!(
+                            "SEARCH block did not match:\n{}",
+                            search.trim_end()
+                        )),
+                    }
+                }
+            }
+        },
+    }
+
+    failures
+}
+
+/// Options for `dircat apply`
+pub struct ApplyOptions {
+    pub dry_run: bool,
+}
+
+/// Outcome of applying a response
+#[derive(Default)]
+pub struct ApplyReport {
+    pub edits: usize,
+    pub files_changed: usize,
+    pub failures: usize,
+}
+
+/// Write every planned file, restoring the originals if any write fails
+fn commit_changes(plan: &[(PathBuf, Option<String>, Option<String>)]) -> io::Result<()> {
+    for (n, (dest, _, updated)) in plan.iter().enumerate() {
+        let result = match updated {
+            Some(text) => write_atomic(dest, text),
+            None => fs::remove_file(dest),
+        };
+        if let Err(err) = result {
+            for (dest, original, _) in plan[..n].iter().rev() {
+                match original {
+                    Some(text) => fs::write(dest, text).ok(),
+                    None => fs::remove_file(dest).ok(),
+                };
+            }
+            return Err(io::Error::new(
+                err.kind(),
+                format!("{}: {}", dest.display(), err),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Write through a temporary file in the same directory and rename it into place
+fn write_atomic(dest: &Path, text: &str) -> io::Result<()> {
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let file_name = dest.file_name().unwrap_or_default().to_string_lossy();
+    let tmp = dest.with_file_name(format!(".{}.dircat-tmp", file_name));
+    fs::write(&tmp, text)?;
+    if let Ok(metadata) = fs::metadata(dest) {
+        fs::set_permissions(&tmp, metadata.permissions()).ok();
+    }
+    fs::rename(&tmp, dest).inspect_err(|_| {
+        fs::remove_file(&tmp).ok();
+    })
+}
+
+/// Validate every edit in `response` against the files under `base_dir` and,
+/// if all of them apply cleanly, write the results
+pub fn apply(response: &str, base_dir: &Path, options: &ApplyOptions) -> io::Result<ApplyReport> {
+    let edits = parse_response(response);
+    let mut report = ApplyReport {
+        edits: edits.len(),
+        ..Default::default()
+    };
+
+    // Group edits by file, keeping first-seen order
+    let mut order: Vec<String> = Vec::new();
+    let mut by_path: BTreeMap<String, Vec<Edit>> = BTreeMap::new();
+    for (path, edit) in edits {
+        if !by_path.contains_key(&path) {
+            order.push(path.clone());
+        }
+        by_path.entry(path).or_default().push(edit);
+    }
+
+    let mut plan = Vec::new();
+    for path in order {
+        let dest = match safe_join(base_dir, &path)
+            .and_then(|d| check_resolved(base_dir, &d, &path).map(|_| d))
+        {
+            Ok(d) => d,
+            Err(e) => {
+                eprintln!("FAILED {}", e);
+                report.failures += 1;
+                continue;
+            }
+        };
+
+        let original = if dest.exists() {
+            Some(
+                fs::read_to_string(&dest)
+                    .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", dest.display(), e)))?,
+            )
+        } else {
+            None
+        };
+
+        let mut content = original.clone();
+        let mut file_failures = Vec::new();
+        for edit in &by_path[&path] {
+            file_failures.extend(apply_edit(&mut content, edit));
+        }
+
+        if !file_failures.is_empty() {
+            for failure in &file_failures {
+                eprintln!("FAILED {}: {}", path, failure);
+            }
+            report.failures += file_failures.len();
+            continue;
+        }
+
+        let action = match (&original, &content) {
+            (a, b) if a == b => "unchanged",
+            (None, Some(_)) => "create",
+            (Some(_), None) => "delete",
+            _ => "update",
+        };
+        eprintln!("{:<9} {}", action, path);
+        if action != "unchanged" {
+            report.files_changed += 1;
+            plan.push((dest, original, content));
+        }
+    }
+
+    if report.failures == 0 && !options.dry_run {
+        commit_changes(&plan)?;
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parse `response` and apply its edits to one file's contents, returning
+    /// the paths the edits named, the result and any failures
+    fn apply_to(
+        response: &str,
+        content: Option<&str>,
+    ) -> (Vec<String>, Option<String>, Vec<String>) {
+        let mut content = content.map(str::to_string);
+        let mut paths = Vec::new();
+        let mut failures = Vec::new();
+        for (path, edit) in parse_response(response) {
+            paths.push(path);
+            failures.extend(apply_edit(&mut content, &edit));
+        }
+        (paths, content, failures)
+    }
+
+    const DIFF: &str = "--- a/src/lib.rs\n+++ b/src/lib.rs\n@@ -1,3 +1,3 @@\n fn a() {}\n-fn b() {}\n+fn b() -> u32 { 1 }\n fn c() {}\n";
+
+    const FILE: &str = "fn a() {}\nfn b() {}\nfn c() {}\n";
+
+    #[test]
+    fn bare_diff() {
+        let response = format!("Here is the change:\n\n{}\nThat's all.\n", DIFF);
+        let (paths, content, failures) = apply_to(&response, Some(FILE));
+        assert_eq!(paths, ["src/lib.rs"]);
+        assert!(failures.is_empty(), "{:?}", failures);
+        assert_eq!(
+            content.unwrap(),
+            "fn a() {}\nfn b() -> u32 { 1 }\nfn c() {}\n"
+        );
+    }
+
+    #[test]
+    fn fenced_diff() {
+        let response = format!("```diff\n{}```\n", DIFF);
+        let (paths, content, failures) = apply_to(&response, Some(FILE));
+        assert_eq!(paths, ["src/lib.rs"]);
+        assert!(failures.is_empty(), "{:?}", failures);
+        assert_eq!(
+            content.unwrap(),
+            "fn a() {}\nfn b() -> u32 { 1 }\nfn c() {}\n"
+        );
+    }
+
+    #[test]
+    fn diff_from_dev_null_creates_the_file() {
+        let response = "--- /dev/null\n+++ b/new.txt\n@@ -0,0 +1,2 @@\n+one\n+two\n";
+        let (paths, content, failures) = apply_to(response, None);
+        assert_eq!(paths, ["new.txt"]);
+        assert!(failures.is_empty(), "{:?}", failures);
+        assert_eq!(content.as_deref(), Some("one\ntwo\n"));
+    }
+
+    #[test]
+    fn diff_to_dev_null_deletes_the_file() {
+        let response = "--- a/old.txt\n+++ /dev/null\n@@ -1,2 +0,0 @@\n-one\n-two\n";
+        let (paths, content, failures) = apply_to(response, Some("one\ntwo\n"));
+        assert_eq!(paths, ["old.txt"]);
+        assert!(failures.is_empty(), "{:?}", failures);
+        assert_eq!(content, None);
+    }
+
+    #[test]
+    fn hunk_matches_despite_trailing_whitespace_and_stale_line_numbers() {
+        let file = "// header\n\nfn a() {}  \nfn b() {}\t\nfn c() {}\n";
+        let response = DIFF.replace("@@ -1,3 +1,3 @@", "@@ -40,3 +40,3 @@");
+        let (_, content, failures) = apply_to(&response, Some(file));
+        assert!(failures.is_empty(), "{:?}", failures);
+        assert_eq!(
+            content.unwrap(),
+            "// header\n\nfn a() {}\nfn b() -> u32 { 1 }\nfn c() {}\n"
+        );
+    }
+
+    #[test]
+    fn hunk_that_does_not_match_fails() {
+        let (_, content, failures) = apply_to(DIFF, Some("fn x() {}\n"));
+        assert_eq!(failures.len(), 1);
+        assert_eq!(content.as_deref(), Some("fn x() {}\n"));
+    }
+
+    #[test]
+    fn crlf_file_keeps_its_line_endings() {
+        let (_, content, failures) = apply_to(DIFF, Some(&FILE.replace('\n', "\r\n")));
+        assert!(failures.is_empty(), "{:?}", failures);
+        assert_eq!(
+            content.unwrap(),
+            "fn a() {}\r\nfn b() -> u32 { 1 }\r\nfn c() {}\r\n"
+        );
+    }
+
+    #[test]
+    fn search_replace_after_path_line() {
+        let response = "src/lib.rs\n```rust\n<<<<<<< SEARCH\nfn b() {}\n=======\nfn b() -> u32 { 1 }\n>>>>>>> REPLACE\n```\n";
+        let (paths, content, failures) = apply_to(response, Some(FILE));
+        assert_eq!(paths, ["src/lib.rs"]);
+        assert!(failures.is_empty(), "{:?}", failures);
+        assert_eq!(
+            content.unwrap(),
+            "fn a() {}\nfn b() -> u32 { 1 }\nfn c() {}\n"
+        );
+    }
+
+    #[test]
+    fn whole_file_under_heading() {
+        let response = "### src/new.rs\n\n```rust\nfn main() {}\n```\n";
+        let (paths, content, failures) = apply_to(response, None);
+        assert_eq!(paths, ["src/new.rs"]);
+        assert!(failures.is_empty(), "{:?}", failures);
+        assert_eq!(content.as_deref(), Some("fn main() {}\n"));
+    }
+
+    #[test]
+    fn failed_search_block_writes_nothing() {
+        let base = std::env::temp_dir().join(format!("dircat-apply-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&base);
+        fs::create_dir_all(&base).unwrap();
+        fs::write(base.join("a.txt"), "alpha\n").unwrap();
+        fs::write(base.join("b.txt"), "beta\n").unwrap();
+
+        // The first edit would apply, but the second can't, so neither is written
+        let response = "a.txt\n<<<<<<< SEARCH\nalpha\n=======\nALPHA\n>>>>>>> REPLACE\n\nb.txt\n<<<<<<< SEARCH\ngamma\n=======\nGAMMA\n>>>>>>> REPLACE\n";
+        let report = apply(response, &base, &ApplyOptions { dry_run: false }).unwrap();
+        assert_eq!(report.edits, 2);
+        assert_eq!(report.failures, 1);
+        assert_eq!(fs::read_to_string(base.join("a.txt")).unwrap(), "alpha\n");
+        assert_eq!(fs::read_to_string(base.join("b.txt")).unwrap(), "beta\n");
+        let _ = fs::remove_dir_all(&base);
+    }
+}
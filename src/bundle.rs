@@ -0,0 +1,876 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use encoding_rs::Encoding;
+use globset::GlobSet;
+use rayon::ThreadPool;
+use rayon::prelude::*;
+use same_file::Handle;
+
+use crate::collect::{self, FileSource, SourceFile};
+use crate::content::{self, FileContent, ReadOptions};
+use crate::error::Error;
+use crate::git::{self, ChangeSelection, FileDiff, PendingChanges};
+use crate::json::{self, JsonLinesWriter, JsonWriter};
+use crate::markdown::{self, MarkdownWriter};
+use crate::record::{self, FileRecord};
+use crate::sensitive;
+use crate::tokens::{self, Tokenizer};
+use crate::tree;
+use crate::writer::BundleWriter;
+use crate::xml::{self, XmlWriter};
+
+/// Output formats for the generated bundle
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Format {
+    Markdown,
+    Xml,
+    Json,
+    JsonLines,
+}
+
+impl Format {
+    /// Look up a format by its command-line name
+    pub fn from_name(name: &str) -> Option<Format> {
+        match name.to_lowercase().as_str() {
+            "markdown" | "md" => Some(Format::Markdown),
+            "xml" => Some(Format::Xml),
+            "json" => Some(Format::Json),
+            "jsonl" | "ndjson" => Some(Format::JsonLines),
+            _ => None,
+        }
+    }
+
+    /// The built-in writer for this format
+    pub fn writer<'a, W: Write + 'a>(self, writer: W) -> Box<dyn BundleWriter + 'a> {
+        match self {
+            Format::Markdown => Box::new(MarkdownWriter::new(writer)),
+            Format::Xml => Box::new(XmlWriter::new(writer)),
+            Format::Json => Box::new(JsonWriter::new(writer)),
+            Format::JsonLines => Box::new(JsonLinesWriter::new(writer)),
+        }
+    }
+
+    /// Output file used when `--output` isn't given
+    pub fn default_output(self) -> &'static str {
+        match self {
+            Format::Markdown => "output.md",
+            Format::Xml => "output.xml",
+            Format::Json => "output.json",
+            Format::JsonLines => "output.jsonl",
+        }
+    }
+}
+
+/// How many files are read in parallel ahead of the writers
+const READ_AHEAD: usize = 256;
+
+/// Default exclusions for common build/dependency directories
+const DEFAULT_EXCLUDES: &[&str] = &[
+    "target",
+    "node_modules",
+    "__pycache__",
+    ".git",
+    "dist",
+    "build",
+    "vendor",
+    "*.lock",
+];
+
+/// Something worth telling the user about that doesn't stop the bundle
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Notice {
+    /// A file was left out because it is itself a dircat bundle
+    SkippedBundle(PathBuf),
+    /// A file was decoded from something other than UTF-8, or had invalid bytes replaced
+    Decoded {
+        path: PathBuf,
+        encoding: &'static Encoding,
+        lossy: bool,
+    },
+    /// A file couldn't be read; the bundle carries the error in its place
+    ReadError { path: PathBuf, error: String },
+}
+
+impl fmt::Display for Notice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Notice::SkippedBundle(path) => {
+                write!(f, "Skipping {}: it is a dircat bundle", path.display())
+            }
+            Notice::Decoded {
+                path,
+                encoding,
+                lossy,
+            } => write!(
+                f,
+                "Decoded {} as {}{}",
+                path.display(),
+                encoding.name(),
+                if *lossy {
+                    " (invalid bytes replaced)"
+                } else {
+                    ""
+                }
+            ),
+            Notice::ReadError { path, error } => {
+                write!(f, "Error reading {}: {}", path.display(), error)
+            }
+        }
+    }
+}
+
+/// What the checks in [`Bundler::collect`] decided about a file
+enum Check {
+    Keep,
+    /// The file being written
+    Output,
+    /// An earlier bundle
+    Bundle,
+    /// Possibly secrets, for the given reason
+    Sensitive(&'static str),
+}
+
+type NoticeHandler = Arc<dyn Fn(&Notice) + Send + Sync>;
+
+/// Where a bundle is being written, so it can be kept out of itself
+#[derive(Clone, Debug)]
+pub enum OutputTarget {
+    File(PathBuf),
+    Stdout,
+}
+
+/// Token counts made while collecting, with `count_tokens` or `max_tokens`
+pub struct TokenReport {
+    /// Name of the tokenizer used, e.g. "cl100k"
+    pub tokenizer: &'static str,
+    /// Tokens each selected file contributes, before any were dropped
+    pub counts: Vec<(PathBuf, usize)>,
+    /// Files dropped to fit the budget, in priority order
+    pub dropped: Vec<(PathBuf, usize)>,
+    /// Total tokens of the files kept
+    pub total: usize,
+}
+
+/// Configures which files go into a bundle and how it is rendered
+///
+/// Setters take `&mut self` so a `Bundler` can be configured in steps and
+/// reused; [`Bundler::collect`] walks the directory and returns a [`Bundle`].
+#[derive(Clone)]
+pub struct Bundler {
+    root: PathBuf,
+    include: Vec<String>,
+    exclude: Vec<String>,
+    ignore_files: bool,
+    rev: Option<String>,
+    changes: Vec<ChangeSelection>,
+    diff_base: Option<String>,
+    max_tokens: Option<usize>,
+    priority: Vec<Vec<String>>,
+    tokenizer: Option<Tokenizer>,
+    count_tokens: bool,
+    read_options: ReadOptions,
+    tree: bool,
+    tree_omitted: bool,
+    toc: bool,
+    format: Format,
+    outputs: Vec<OutputTarget>,
+    on_notice: Option<NoticeHandler>,
+    jobs: usize,
+    allow_sensitive: bool,
+}
+
+impl Bundler {
+    /// Bundle files under `root`, honoring ignore files, as Markdown
+    pub fn new<P: AsRef<Path>>(root: P) -> Bundler {
+        Bundler {
+            root: root.as_ref().to_path_buf(),
+            include: Vec::new(),
+            exclude: Vec::new(),
+            ignore_files: true,
+            rev: None,
+            changes: Vec::new(),
+            diff_base: None,
+            max_tokens: None,
+            priority: Vec::new(),
+            tokenizer: None,
+            count_tokens: false,
+            read_options: ReadOptions::default(),
+            tree: false,
+            tree_omitted: false,
+            toc: false,
+            format: Format::Markdown,
+            outputs: Vec::new(),
+            on_notice: None,
+            jobs: 0,
+            allow_sensitive: false,
+        }
+    }
+
+    /// Add glob patterns for files to include, matched against the file name or relative path
+    pub fn include<I, S>(&mut self, patterns: I) -> &mut Bundler
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.include.extend(patterns.into_iter().map(Into::into));
+        self
+    }
+
+    /// Add glob patterns to exclude, on top of the default build and dependency directories
+    pub fn exclude<I, S>(&mut self, patterns: I) -> &mut Bundler
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.exclude.extend(patterns.into_iter().map(Into::into));
+        self
+    }
+
+    /// Whether to honor `.gitignore`, `.ignore` and `.dircatignore` files (default true)
+    pub fn ignore_files(&mut self, yes: bool) -> &mut Bundler {
+        self.ignore_files = yes;
+        self
+    }
+
+    /// Read files from a git revision instead of the working directory
+    pub fn rev<S: Into<String>>(&mut self, rev: S) -> &mut Bundler {
+        self.rev = Some(rev.into());
+        self
+    }
+
+    /// Only bundle files that are part of these changes; selections accumulate
+    pub fn select_changes(&mut self, selection: ChangeSelection) -> &mut Bundler {
+        self.changes.push(selection);
+        self
+    }
+
+    /// Append a diff of each bundled file against `base`
+    ///
+    /// Without a change selection, this also restricts the bundle to the files
+    /// that differ from `base`.
+    pub fn with_diff<S: Into<String>>(&mut self, base: S) -> &mut Bundler {
+        self.diff_base = Some(base.into());
+        self
+    }
+
+    /// Drop files until the bundle fits in `max` tokens
+    pub fn max_tokens(&mut self, max: usize) -> &mut Bundler {
+        self.max_tokens = Some(max);
+        self
+    }
+
+    /// Keep files that look like they hold secrets, such as `.env` files and
+    /// private keys, instead of leaving them out
+    ///
+    /// Either way they are listed in [`Bundle::sensitive`].
+    pub fn allow_sensitive(&mut self, yes: bool) -> &mut Bundler {
+        self.allow_sensitive = yes;
+        self
+    }
+
+    /// Add a tier of patterns to keep first under `max_tokens`; earlier tiers win
+    pub fn priority<I, S>(&mut self, patterns: I) -> &mut Bundler
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.priority
+            .push(patterns.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Tokenizer for `max_tokens` and `count_tokens` (default cl100k)
+    pub fn tokenizer(&mut self, tokenizer: Tokenizer) -> &mut Bundler {
+        self.tokenizer = Some(tokenizer);
+        self
+    }
+
+    /// Count every file's tokens into a [`TokenReport`], even without a budget
+    pub fn count_tokens(&mut self, yes: bool) -> &mut Bundler {
+        self.count_tokens = yes;
+        self
+    }
+
+    /// Embed binary files up to `max` bytes as base64 instead of a placeholder
+    pub fn embed_binary(&mut self, max: u64) -> &mut Bundler {
+        self.read_options.embed_binary_max = Some(max);
+        self
+    }
+
+    /// Decode files without a byte order mark using this encoding instead of detecting one
+    pub fn encoding(&mut self, encoding: &'static Encoding) -> &mut Bundler {
+        self.read_options.encoding = Some(encoding);
+        self
+    }
+
+    /// Replace undecodable bytes with U+FFFD instead of failing
+    pub fn lossy(&mut self, yes: bool) -> &mut Bundler {
+        self.read_options.lossy = yes;
+        self
+    }
+
+    /// Start the bundle with a directory tree of the collected files
+    pub fn tree(&mut self, yes: bool) -> &mut Bundler {
+        self.tree = yes;
+        self
+    }
+
+    /// Like `tree`, but also list excluded directories, marked as omitted
+    pub fn tree_omitted(&mut self, yes: bool) -> &mut Bundler {
+        self.tree_omitted = yes;
+        self
+    }
+
+    /// Start Markdown output with a linked table of contents
+    pub fn toc(&mut self, yes: bool) -> &mut Bundler {
+        self.toc = yes;
+        self
+    }
+
+    pub fn format(&mut self, format: Format) -> &mut Bundler {
+        self.format = format;
+        self
+    }
+
+    /// Where the bundle will be written, so that file is never bundled into itself;
+    /// call once for each destination
+    pub fn output(&mut self, target: OutputTarget) -> &mut Bundler {
+        self.outputs.push(target);
+        self
+    }
+
+    /// Walk the directory and read files on `jobs` threads; 0 (the default) picks
+    /// one per CPU. The bundle comes out the same whatever the number.
+    pub fn jobs(&mut self, jobs: usize) -> &mut Bundler {
+        self.jobs = jobs;
+        self
+    }
+
+    /// Call `handler` for every [`Notice`] raised while collecting or writing
+    pub fn on_notice<F>(&mut self, handler: F) -> &mut Bundler
+    where
+        F: Fn(&Notice) + Send + Sync + 'static,
+    {
+        self.on_notice = Some(Arc::new(handler));
+        self
+    }
+
+    fn notify(&self, notice: Notice) {
+        if let Some(handler) = &self.on_notice {
+            handler(&notice);
+        }
+    }
+
+    /// Walk the base directory (or revision), apply every filter and limit, and
+    /// gather what's needed to write the bundle
+    pub fn collect(&self) -> Result<Bundle, Error> {
+        let root = &self.root;
+        if !root.is_dir() {
+            return Err(Error::NotADirectory(root.clone()));
+        }
+
+        let include: Vec<String> = self
+            .include
+            .iter()
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .collect();
+        if include.is_empty() {
+            return Err(Error::NoIncludePatterns);
+        }
+        let mut exclude: Vec<String> = DEFAULT_EXCLUDES.iter().map(|s| s.to_string()).collect();
+        exclude.extend(self.exclude.iter().cloned());
+
+        let include_glob =
+            collect::build_globset(&include).map_err(|source| Error::InvalidPattern {
+                kind: "include",
+                source,
+            })?;
+        let exclude_glob =
+            collect::build_globset(&exclude).map_err(|source| Error::InvalidPattern {
+                kind: "exclude",
+                source,
+            })?;
+        let priority_globs = self
+            .priority
+            .iter()
+            .map(|patterns| {
+                collect::build_globset(patterns).map_err(|source| Error::InvalidPattern {
+                    kind: "priority",
+                    source,
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let (mut files, pruned_dirs) = match &self.rev {
+            Some(rev) => collect::collect_rev_files(root, rev, &include_glob, &exclude_glob)
+                .map_err(Error::Git)?,
+            None => collect::collect_files(
+                root,
+                &include_glob,
+                &exclude_glob,
+                self.ignore_files,
+                self.jobs,
+            ),
+        };
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(self.jobs)
+            .build()
+            .map_err(|e| Error::Threads(e.to_string()))?;
+
+        // Never bundle the file being written, nor earlier bundles
+        let outputs: Vec<Handle> = self
+            .outputs
+            .iter()
+            .filter_map(|target| match target {
+                OutputTarget::File(path) => Handle::from_path(path).ok(),
+                // `dircat . "*.md" > out.md` creates out.md before dircat even starts
+                OutputTarget::Stdout => Handle::stdout()
+                    .ok()
+                    .filter(|h| h.as_file().metadata().is_ok_and(|m| m.is_file())),
+            })
+            .collect();
+        let is_output = |file: &SourceFile| match &file.source {
+            FileSource::Disk(path) => {
+                !outputs.is_empty() && Handle::from_path(path).is_ok_and(|h| outputs.contains(&h))
+            }
+            FileSource::Memory { .. } => false,
+        };
+        // Recognizing bundles and private keys means reading the file's first
+        // bytes, so it runs on the pool
+        let checks: Vec<Check> = pool.install(|| {
+            files
+                .par_iter()
+                .map(|file| {
+                    if is_output(file) {
+                        return Check::Output;
+                    }
+                    let head = collect::read_head(file, sensitive::SNIFF_LEN);
+                    if collect::is_bundle(&head[..head.len().min(collect::BUNDLE_SNIFF_LEN)]) {
+                        return Check::Bundle;
+                    }
+                    match sensitive::check(&file.rel_path, &head) {
+                        Some(reason) => Check::Sensitive(reason),
+                        None => Check::Keep,
+                    }
+                })
+                .collect()
+        });
+        let mut checks = checks.into_iter();
+        let mut sensitive = Vec::new();
+        files.retain(|file| match checks.next().unwrap_or(Check::Keep) {
+            Check::Output => false,
+            Check::Bundle => {
+                self.notify(Notice::SkippedBundle(file.rel_path.clone()));
+                false
+            }
+            Check::Sensitive(reason) => {
+                sensitive.push((file.rel_path.clone(), reason));
+                true
+            }
+            Check::Keep => true,
+        });
+
+        // Without an explicit selection, a diff base bundles the files that have a diff
+        let mut selections = self.changes.clone();
+        if selections.is_empty()
+            && let Some(base) = &self.diff_base
+        {
+            selections.push(ChangeSelection::ChangedSince(base.clone()));
+        }
+        if !selections.is_empty() {
+            let filter = git::ChangeFilter::new(root, &selections).map_err(Error::Git)?;
+            files.retain(|file| filter.matches(file));
+            sensitive.retain(|(path, _)| files.iter().any(|f| &f.rel_path == path));
+        }
+        if !self.allow_sensitive {
+            files.retain(|file| !sensitive.iter().any(|(path, _)| *path == file.rel_path));
+        }
+
+        let mut bundle = Bundle {
+            files,
+            tree: None,
+            toc: self.toc,
+            changes: None,
+            read_options: self.read_options.clone(),
+            format: self.format,
+            token_report: None,
+            sensitive,
+            on_notice: self.on_notice.clone(),
+            pool: Arc::new(pool),
+            include: include_glob,
+            exclude: exclude_glob,
+        };
+
+        // Diffs are part of what the budget has to fit, so they come first
+        if let Some(base) = &self.diff_base {
+            // A diff shows the old contents too, so they are held to the same
+            // secrets check as the files
+            let mut base_sensitive = Vec::new();
+            let mut keep_diff = |rel_path: &Path, old: &[u8]| {
+                if !bundle.matches_patterns(rel_path) {
+                    return false;
+                }
+                let head = &old[..old.len().min(sensitive::SNIFF_LEN as usize)];
+                match sensitive::check(rel_path, head) {
+                    Some(reason) => {
+                        if !bundle.sensitive.iter().any(|(path, _)| path == rel_path) {
+                            base_sensitive.push((rel_path.to_path_buf(), reason));
+                        }
+                        self.allow_sensitive
+                    }
+                    None => true,
+                }
+            };
+            let changes = git::pending_changes(root, base, &bundle.files, &mut keep_diff)
+                .map_err(Error::Git)?;
+            bundle.changes = Some(changes);
+            if !base_sensitive.is_empty() {
+                bundle.sensitive.extend(base_sensitive);
+                bundle.sensitive.sort();
+            }
+        }
+
+        // JSON writers have no place for a tree and leave it out
+        let omitted: &[PathBuf] = if self.tree_omitted { &pruned_dirs } else { &[] };
+        let label = format!("{}/", root.display().to_string().trim_end_matches('/'));
+        if self.tree || self.tree_omitted {
+            bundle.tree = Some(tree::render_tree(&label, &bundle, omitted));
+        }
+
+        if self.count_tokens || self.max_tokens.is_some() {
+            let tokenizer = self.tokenizer.unwrap_or_default();
+            let mut token_counts = count_file_tokens(&bundle, &tokenizer);
+            let files = &bundle.files;
+            let mut paths: Vec<&Path> = files.iter().map(|f| f.rel_path.as_path()).collect();
+            // Diffs of deleted files have no file to ride along with, so they
+            // are budgeted on their own
+            let deleted: Vec<&FileDiff> = bundle
+                .changes
+                .iter()
+                .flat_map(|c| &c.diffs)
+                .filter(|d| d.deleted)
+                .collect();
+            for diff in &deleted {
+                paths.push(&diff.rel_path);
+                token_counts.push(diff_tokens(&bundle, paths.len() - 1, diff, &tokenizer));
+            }
+            // The contents, tree and pending changes heading, sized for every
+            // file, so they only shrink as files are dropped
+            let overhead = overhead_tokens(&bundle, &tokenizer);
+
+            let counts = paths
+                .iter()
+                .zip(&token_counts)
+                .map(|(path, &count)| (path.to_path_buf(), count))
+                .collect();
+            let mut report = TokenReport {
+                tokenizer: tokenizer.name(),
+                counts,
+                dropped: Vec::new(),
+                total: token_counts.iter().sum::<usize>() + overhead,
+            };
+            if let Some(max) = self.max_tokens {
+                let selection = tokens::select_within_budget(
+                    &paths,
+                    &token_counts,
+                    &priority_globs,
+                    max.saturating_sub(overhead),
+                );
+                report.dropped = selection
+                    .dropped
+                    .iter()
+                    .map(|&i| (paths[i].to_path_buf(), token_counts[i]))
+                    .collect();
+                report.total = selection.total + overhead;
+
+                if !selection.dropped.is_empty() {
+                    let kept: HashSet<PathBuf> = selection
+                        .kept
+                        .iter()
+                        .map(|&i| paths[i].to_path_buf())
+                        .collect();
+                    let files = selection
+                        .kept
+                        .iter()
+                        .filter(|&&i| i < files.len())
+                        .map(|&i| files[i].clone())
+                        .collect();
+                    bundle.files = files;
+                    if let Some(changes) = &mut bundle.changes {
+                        changes.diffs.retain(|d| kept.contains(&d.rel_path));
+                    }
+                    if bundle.tree.is_some() {
+                        bundle.tree = Some(tree::render_tree(&label, &bundle, omitted));
+                    }
+                }
+            }
+            bundle.token_report = Some(report);
+        }
+
+        Ok(bundle)
+    }
+}
+
+/// Count the tokens each file contributes to the bundle: its section as the
+/// format's writer renders it, and its pending diff
+fn count_file_tokens(bundle: &Bundle, tokenizer: &Tokenizer) -> Vec<usize> {
+    let anchors: Arc<[String]> = if bundle.toc && bundle.format == Format::Markdown {
+        markdown::anchor_ids(&bundle.files).into()
+    } else {
+        Arc::new([])
+    };
+    bundle.pool.install(|| {
+        bundle
+            .files
+            .par_iter()
+            .enumerate()
+            .map(|(i, file)| {
+                let mut section = Vec::new();
+                let mut writer: Box<dyn BundleWriter> = match bundle.format {
+                    Format::Markdown => {
+                        Box::new(MarkdownWriter::with_anchors(&mut section, anchors.clone()))
+                    }
+                    format => format.writer(&mut section),
+                };
+                // Writing to memory can't fail
+                let _ = match content::read_content(file, &bundle.read_options) {
+                    Ok(c) => writer.file(bundle, i, file, &c),
+                    Err(err) => writer.read_error(bundle, i, file, &err),
+                };
+                drop(writer);
+                // JSON records carry their diff, the other formats list it later
+                let diff = match bundle.format {
+                    Format::Json | Format::JsonLines => 0,
+                    _ => bundle
+                        .changes
+                        .as_ref()
+                        .and_then(|c| c.diff_for(&file.rel_path))
+                        .map_or(0, |d| diff_tokens(bundle, i, d, tokenizer)),
+                };
+                tokenizer.count(&String::from_utf8_lossy(&section)) + diff
+            })
+            .collect()
+    })
+}
+
+/// Tokens of one file's entry among the pending changes, or of the `index`th
+/// record for a deleted file in JSON output
+fn diff_tokens(bundle: &Bundle, index: usize, diff: &FileDiff, tokenizer: &Tokenizer) -> usize {
+    let mut entry = Vec::new();
+    // Writing to memory can't fail
+    let _ = match bundle.format {
+        Format::Markdown => markdown::write_diff(&mut entry, diff),
+        Format::Xml => xml::write_diff(&mut entry, diff),
+        format => json::write_deleted(&mut entry, format, index, diff),
+    };
+    tokenizer.count(&String::from_utf8_lossy(&entry))
+}
+
+/// Tokens of everything in the bundle besides the files and their diffs:
+/// the marker, contents, tree and pending changes heading
+fn overhead_tokens(bundle: &Bundle, tokenizer: &Tokenizer) -> usize {
+    let mut preamble = Vec::new();
+    // The writer's `begin` renders exactly what precedes the files; writing to
+    // memory can't fail
+    let _ = bundle.format.writer(&mut preamble).begin(bundle);
+    let mut tokens = tokenizer.count(&String::from_utf8_lossy(&preamble));
+    let changes = bundle.changes.as_ref().filter(|c| !c.diffs.is_empty());
+    match (bundle.format, changes) {
+        (Format::Xml, changes) => tokens += tokenizer.count(&xml::closing_tags(changes)),
+        (Format::Json, _) => tokens += tokenizer.count("\n]\n"),
+        (Format::JsonLines, _) | (Format::Markdown, None) => {}
+        (Format::Markdown, Some(changes)) => {
+            tokens += tokenizer.count(&format!(
+                "\n---\n\n<a id=\"pending-changes\"></a>\n## Pending changes against `{}`\n",
+                changes.base
+            ))
+        }
+    }
+    tokens
+}
+
+/// The collected files and everything needed to render them
+pub struct Bundle {
+    pub(crate) files: Vec<SourceFile>,
+    /// Directory overview written before the files
+    pub(crate) tree: Option<String>,
+    /// Whether Markdown output gets a linked table of contents
+    pub(crate) toc: bool,
+    pub(crate) changes: Option<PendingChanges>,
+    pub(crate) read_options: ReadOptions,
+    format: Format,
+    token_report: Option<TokenReport>,
+    sensitive: Vec<(PathBuf, &'static str)>,
+    on_notice: Option<NoticeHandler>,
+    /// Threads files are read on
+    pub(crate) pool: Arc<ThreadPool>,
+    include: GlobSet,
+    exclude: GlobSet,
+}
+
+impl Bundle {
+    /// The selected files, sorted by path
+    pub fn files(&self) -> &[SourceFile] {
+        &self.files
+    }
+
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    /// Whether a file at `rel_path` would be selected by the include and exclude
+    /// patterns, including the pruning of hidden and excluded directories
+    ///
+    /// Ignore files, change selections and the token budget aren't considered.
+    pub fn matches_patterns(&self, rel_path: &Path) -> bool {
+        let pruned = rel_path
+            .ancestors()
+            .skip(1)
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .any(|dir| collect::is_pruned_dir(dir, &self.exclude));
+        !pruned && collect::is_selected(rel_path, &self.include, &self.exclude)
+    }
+
+    /// The selected file at `rel_path`, if there is one
+    pub fn file(&self, rel_path: &Path) -> Option<&SourceFile> {
+        self.files
+            .binary_search_by(|f| f.rel_path.as_path().cmp(rel_path))
+            .ok()
+            .map(|i| &self.files[i])
+    }
+
+    /// Read one of the bundle's files, decoded the way the bundle decodes it
+    pub fn read(&self, file: &SourceFile) -> io::Result<FileContent> {
+        self.report(file, content::read_content(file, &self.read_options))
+    }
+
+    /// Token counts, if the bundler was asked for a budget or a count
+    pub fn token_report(&self) -> Option<&TokenReport> {
+        self.token_report.as_ref()
+    }
+
+    /// Files that looked like they hold secrets, with the reason, in path order
+    ///
+    /// They are left out of the bundle, and their diffs out of the pending
+    /// changes, unless the bundler allowed them. A file whose contents in the
+    /// diff base look like secrets is listed too, and only its diff withheld.
+    pub fn sensitive(&self) -> &[(PathBuf, &'static str)] {
+        &self.sensitive
+    }
+
+    /// Read each file into the record JSON output is made of, in order
+    pub fn records(&self) -> impl Iterator<Item = FileRecord> + '_ {
+        self.files.chunks(READ_AHEAD).flat_map(move |chunk| {
+            let contents = self.read_chunk(chunk);
+            chunk
+                .iter()
+                .zip(contents)
+                .map(|(file, c)| match self.report(file, c) {
+                    Ok(c) => record::file_record(self, file, Ok(&c)),
+                    Err(err) => record::file_record(self, file, Err(&err)),
+                })
+                .collect::<Vec<_>>()
+        })
+    }
+
+    /// Directory tree rendered for the start of the bundle, if one was asked for
+    pub fn tree(&self) -> Option<&str> {
+        self.tree.as_deref()
+    }
+
+    /// Whether Markdown output gets a linked table of contents
+    pub fn toc(&self) -> bool {
+        self.toc
+    }
+
+    /// Diffs against the `with_diff` base, if one was given
+    pub fn pending_changes(&self) -> Option<&PendingChanges> {
+        self.changes.as_ref()
+    }
+
+    /// Render the bundle in its format
+    pub fn write<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let mut writer = self.format.writer(writer);
+        self.write_to(&mut [writer.as_mut()])
+    }
+
+    /// Render the bundle through several writers at once, reading each file only once
+    pub fn write_to(&self, writers: &mut [&mut dyn BundleWriter]) -> io::Result<()> {
+        for writer in writers.iter_mut() {
+            writer.begin(self)?;
+        }
+        for (n, chunk) in self.files.chunks(READ_AHEAD).enumerate() {
+            let contents = self.read_chunk(chunk);
+            for (j, (file, c)) in chunk.iter().zip(contents).enumerate() {
+                let i = n * READ_AHEAD + j;
+                match self.report(file, c) {
+                    Ok(c) => {
+                        for writer in writers.iter_mut() {
+                            writer.file(self, i, file, &c)?;
+                        }
+                    }
+                    Err(err) => {
+                        for writer in writers.iter_mut() {
+                            writer.read_error(self, i, file, &err)?;
+                        }
+                    }
+                }
+            }
+        }
+        for writer in writers.iter_mut() {
+            writer.end(self)?;
+        }
+        Ok(())
+    }
+
+    fn notify(&self, notice: Notice) {
+        if let Some(handler) = &self.on_notice {
+            handler(&notice);
+        }
+    }
+
+    /// Read a run of files on the thread pool, keeping their order
+    fn read_chunk(&self, chunk: &[SourceFile]) -> Vec<io::Result<FileContent>> {
+        self.pool.install(|| {
+            chunk
+                .par_iter()
+                .map(|file| content::read_content(file, &self.read_options))
+                .collect()
+        })
+    }
+
+    /// Pass on a file's read result, noting any transcoding or failure
+    ///
+    /// Files are read in parallel but reported here in bundle order, so
+    /// notices come out in the same order on every run.
+    fn report(
+        &self,
+        file: &SourceFile,
+        result: io::Result<FileContent>,
+    ) -> io::Result<FileContent> {
+        match &result {
+            Ok(c) => {
+                if let Some(encoding) = c.encoding.filter(|&e| e != encoding_rs::UTF_8 || c.lossy) {
+                    self.notify(Notice::Decoded {
+                        path: file.rel_path.clone(),
+                        encoding,
+                        lossy: c.lossy,
+                    });
+                }
+            }
+            Err(err) => {
+                self.notify(Notice::ReadError {
+                    path: file.rel_path.clone(),
+                    error: err.to_string(),
+                });
+            }
+        }
+        result
+    }
+}
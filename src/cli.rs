@@ -0,0 +1,279 @@
+use std::ffi::OsString;
+use std::path::PathBuf;
+
+use clap::{Args, CommandFactory, Parser, Subcommand};
+use clap_complete::Shell;
+
+/// Concatenate source files into a single Markdown, XML or JSON bundle for LLMs
+#[derive(Parser)]
+#[command(name = "dircat", version, arg_required_else_help = true)]
+#[command(
+    after_help = "Running `dircat <directory> [patterns] [options]` without a subcommand is the same as `dircat pack ...`."
+)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Bundle matching files into one document (the default)
+    Pack(PackArgs),
+    /// List the files that would be bundled
+    List(SelectArgs),
+    /// Show size, line and token counts for the files that would be bundled
+    Stats(StatsArgs),
+    /// Serve bundles of the directory over HTTP on localhost
+    Serve(ServeArgs),
+    /// Run a Model Context Protocol server for the directory on stdin and stdout
+    Mcp(McpArgs),
+    /// Recreate files from a bundle
+    Unpack(UnpackArgs),
+    /// Apply the edits in an LLM response to the working tree
+    Apply(ApplyArgs),
+    /// Print a shell completion script
+    Completions {
+        /// Shell to generate completions for
+        shell: Shell,
+    },
+    /// Print the man page in roff format
+    Man,
+}
+
+/// Which files to collect
+#[derive(Args)]
+pub struct SelectArgs {
+    /// The root directory to scan
+    pub directory: PathBuf,
+
+    /// Comma-separated glob patterns for files to include
+    pub patterns: Option<String>,
+
+    /// Additional glob pattern to exclude (repeatable)
+    #[arg(long, value_name = "PATTERN")]
+    pub exclude: Vec<String>,
+
+    /// Don't honor .gitignore, .ignore and .dircatignore files
+    #[arg(long)]
+    pub no_ignore: bool,
+
+    /// Include files that look like secrets (.env, private keys, .npmrc, ...) instead of skipping them
+    #[arg(long)]
+    pub allow_sensitive: bool,
+
+    /// Threads for walking and reading files [default: one per CPU]
+    #[arg(short, long, value_name = "N")]
+    pub jobs: Option<usize>,
+
+    /// Apply the named [profile.<name>] from the config files
+    #[arg(long, value_name = "NAME", conflicts_with = "no_config")]
+    pub profile: Option<String>,
+
+    /// Use this file instead of the discovered dircat.toml
+    #[arg(long, value_name = "FILE")]
+    pub config: Option<PathBuf>,
+
+    /// Ignore all config files
+    #[arg(long)]
+    pub no_config: bool,
+
+    /// Only files that differ from REV in the working tree
+    #[arg(long, value_name = "REV", help_heading = "Git selection")]
+    pub changed_since: Vec<String>,
+
+    /// Only files with staged changes
+    #[arg(long, help_heading = "Git selection")]
+    pub staged: bool,
+
+    /// Only files with staged, unstaged or untracked changes
+    #[arg(long, help_heading = "Git selection")]
+    pub uncommitted: bool,
+
+    /// Only files changed between two revisions (A..B), or in one commit
+    #[arg(long, value_name = "A..B|COMMIT", help_heading = "Git selection")]
+    pub commits: Vec<String>,
+
+    /// Read files from a git revision instead of the working directory
+    #[arg(long, value_name = "REV", help_heading = "Git selection")]
+    pub rev: Option<String>,
+}
+
+/// How file contents are decoded
+#[derive(Args)]
+pub struct ReadArgs {
+    /// Decode files without a byte order mark using this encoding
+    #[arg(long, value_name = "LABEL")]
+    pub encoding: Option<String>,
+
+    /// Replace undecodable bytes with U+FFFD instead of failing
+    #[arg(long)]
+    pub lossy: bool,
+
+    /// Embed binary files up to this size (e.g. 64K) as base64
+    #[arg(long, value_name = "MAX_SIZE")]
+    pub embed_binary: Option<String>,
+}
+
+#[derive(Args)]
+pub struct PackArgs {
+    #[command(flatten)]
+    pub select: SelectArgs,
+
+    #[command(flatten)]
+    pub read: ReadArgs,
+
+    /// Output file [default: output.md, output.xml, output.json or output.jsonl]
+    #[arg(short, long, value_name = "FILE")]
+    pub output: Option<String>,
+
+    /// Overwrite the output file even if dircat didn't create it
+    #[arg(long)]
+    pub force: bool,
+
+    /// Output format: markdown, xml, json or jsonl
+    #[arg(short, long, value_name = "FORMAT")]
+    pub format: Option<String>,
+
+    /// Also write the bundle in another format, e.g. jsonl:out.jsonl (repeatable)
+    #[arg(long, value_name = "FORMAT:FILE")]
+    pub tee: Vec<String>,
+
+    /// Drop files until the bundle fits in N tokens
+    #[arg(long, value_name = "N", help_heading = "Token budget")]
+    pub max_tokens: Option<usize>,
+
+    /// Comma-separated patterns to keep first under --max-tokens (repeatable, earliest wins)
+    #[arg(long, value_name = "PATTERNS", help_heading = "Token budget")]
+    pub priority: Vec<String>,
+
+    /// Tokenizer: cl100k (default), o200k or estimate
+    #[arg(long, value_name = "NAME", help_heading = "Token budget")]
+    pub tokenizer: Option<String>,
+
+    /// Print per-file token counts to stderr
+    #[arg(long, help_heading = "Token budget")]
+    pub count_tokens: bool,
+
+    /// Append a unified diff of each bundled file against HEAD
+    #[arg(long, help_heading = "Git selection")]
+    pub with_diff: bool,
+
+    /// Like --with-diff, but diff against REV
+    #[arg(long, value_name = "REV", help_heading = "Git selection")]
+    pub diff_base: Option<String>,
+
+    /// Start the bundle with a directory tree of the collected files
+    #[arg(long)]
+    pub tree: bool,
+
+    /// Like --tree, but also list excluded directories, marked as omitted
+    #[arg(long)]
+    pub tree_omitted: bool,
+
+    /// Start Markdown output with a linked table of contents
+    #[arg(long)]
+    pub toc: bool,
+
+    /// Keep running and rewrite the output whenever a matching file changes
+    #[arg(short, long)]
+    pub watch: bool,
+}
+
+#[derive(Args)]
+pub struct StatsArgs {
+    #[command(flatten)]
+    pub select: SelectArgs,
+
+    #[command(flatten)]
+    pub read: ReadArgs,
+
+    /// Tokenizer: cl100k (default), o200k or estimate
+    #[arg(long, value_name = "NAME")]
+    pub tokenizer: Option<String>,
+}
+
+#[derive(Args)]
+pub struct ServeArgs {
+    #[command(flatten)]
+    pub select: SelectArgs,
+
+    #[command(flatten)]
+    pub read: ReadArgs,
+
+    /// Port to listen on at 127.0.0.1
+    #[arg(long, value_name = "PORT", default_value_t = 8080)]
+    pub port: u16,
+
+    /// Format /bundle returns when the request doesn't name one
+    #[arg(short, long, value_name = "FORMAT")]
+    pub format: Option<String>,
+
+    /// Tokenizer for the max_tokens parameter: cl100k (default), o200k or estimate
+    #[arg(long, value_name = "NAME")]
+    pub tokenizer: Option<String>,
+}
+
+#[derive(Args)]
+pub struct McpArgs {
+    #[command(flatten)]
+    pub select: SelectArgs,
+
+    #[command(flatten)]
+    pub read: ReadArgs,
+
+    /// Tokenizer for read_bundle's max_tokens: cl100k (default), o200k or estimate
+    #[arg(long, value_name = "NAME")]
+    pub tokenizer: Option<String>,
+}
+
+#[derive(Args)]
+pub struct UnpackArgs {
+    /// Bundle to read, or - for stdin
+    pub bundle: String,
+
+    /// Directory to write the files into
+    #[arg(short = 'd', long = "dir", value_name = "TARGET", default_value = ".")]
+    pub target: PathBuf,
+
+    /// Show what would be written without touching any files
+    #[arg(short = 'n', long)]
+    pub dry_run: bool,
+
+    /// Print a unified diff of every change to stdout
+    #[arg(long)]
+    pub diff: bool,
+}
+
+#[derive(Args)]
+pub struct ApplyArgs {
+    /// Response to read, or - for stdin
+    pub response: String,
+
+    /// Directory the edits' paths are relative to
+    #[arg(short = 'd', long = "dir", value_name = "BASE", default_value = ".")]
+    pub base_dir: PathBuf,
+
+    /// Check that every edit applies without changing any files
+    #[arg(short = 'n', long)]
+    pub dry_run: bool,
+}
+
+/// Parse the command line, treating a leading directory as `dircat pack`
+///
+/// `dircat <directory> <patterns> [options]` predates the subcommands, so when
+/// the first argument isn't a subcommand or a top-level flag, `pack` is implied.
+pub fn parse() -> Cli {
+    let mut args: Vec<OsString> = std::env::args_os().collect();
+    if let Some(first) = args.get(1).map(|a| a.to_string_lossy().into_owned()) {
+        let command = Cli::command();
+        let is_subcommand = first == "help"
+            || command
+                .get_subcommands()
+                .any(|c| c.get_name() == first || c.get_all_aliases().any(|a| a == first));
+        let is_top_level_flag = matches!(first.as_str(), "-h" | "--help" | "-V" | "--version");
+        if !is_subcommand && !is_top_level_flag {
+            args.insert(1, OsString::from("pack"));
+        }
+    }
+    Cli::parse_from(args)
+}
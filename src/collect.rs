@@ -0,0 +1,274 @@
+use std::collections::BTreeSet;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use ignore::{DirEntry, WalkBuilder, WalkState};
+
+use crate::git;
+
+/// Build a GlobSet from string patterns
+pub(crate) fn build_globset(patterns: &[String]) -> Result<GlobSet, globset::Error> {
+    let mut builder = GlobSetBuilder::new();
+    for pat in patterns {
+        builder.add(Glob::new(pat)?);
+    }
+    builder.build()
+}
+
+/// Decide whether to prune a directory (skip recursion)
+fn should_prune_dir(entry: &DirEntry, base: &Path, exclude: &GlobSet) -> bool {
+    // Don't prune the base directory itself
+    if entry.path() == base {
+        return false;
+    }
+
+    let rel_path = match entry.path().strip_prefix(base) {
+        Ok(p) => p,
+        Err(_) => return false,
+    };
+
+    is_pruned_dir(rel_path, exclude)
+}
+
+/// Decide whether a directory, given relative to the base, is hidden or excluded
+pub(crate) fn is_pruned_dir(rel_path: &Path, exclude: &GlobSet) -> bool {
+    let name = rel_path.file_name().unwrap_or_default();
+
+    // Skip dot-directories (hidden directories)
+    if name.to_string_lossy().starts_with('.') {
+        return true;
+    }
+
+    let dot_rel = PathBuf::from(".").join(rel_path);
+
+    exclude.is_match(name) || exclude.is_match(rel_path) || exclude.is_match(&dot_rel)
+}
+
+/// Decide whether a file, given relative to the base, matches the include patterns and no exclude
+pub(crate) fn is_selected(rel_path: &Path, include: &GlobSet, exclude: &GlobSet) -> bool {
+    let file_name = rel_path.file_name().unwrap_or_default();
+
+    (include.is_match(file_name) || include.is_match(rel_path))
+        && !exclude.is_match(file_name)
+        && !exclude.is_match(rel_path)
+}
+
+/// Where a collected file's contents come from
+#[derive(Clone)]
+pub(crate) enum FileSource {
+    /// A file in the working directory
+    Disk(PathBuf),
+    /// Contents already in memory, such as a blob read from a git revision
+    Memory {
+        data: Arc<[u8]>,
+        modified: Option<SystemTime>,
+    },
+}
+
+/// A file selected for the bundle
+#[derive(Clone)]
+pub struct SourceFile {
+    /// Path relative to the base directory, as shown in headings
+    pub rel_path: PathBuf,
+    pub(crate) source: FileSource,
+}
+
+impl SourceFile {
+    pub(crate) fn open(&self) -> io::Result<Box<dyn Read + '_>> {
+        match &self.source {
+            FileSource::Disk(path) => Ok(Box::new(File::open(path)?)),
+            FileSource::Memory { data, .. } => Ok(Box::new(&data[..])),
+        }
+    }
+
+    /// Read the whole file into memory
+    pub fn read_bytes(&self) -> io::Result<Vec<u8>> {
+        match &self.source {
+            FileSource::Disk(path) => fs::read(path),
+            FileSource::Memory { data, .. } => Ok(data.to_vec()),
+        }
+    }
+
+    /// Size in bytes, if known
+    pub fn size(&self) -> Option<u64> {
+        match &self.source {
+            FileSource::Disk(path) => fs::metadata(path).ok().map(|m| m.len()),
+            FileSource::Memory { data, .. } => Some(data.len() as u64),
+        }
+    }
+
+    /// Last modification time, or the commit time for a file read from a revision
+    pub fn modified(&self) -> Option<SystemTime> {
+        match &self.source {
+            FileSource::Disk(path) => fs::metadata(path).and_then(|m| m.modified()).ok(),
+            FileSource::Memory { modified, .. } => *modified,
+        }
+    }
+}
+
+/// Name of the dircat-specific ignore file, honored alongside .gitignore and .ignore
+const DIRCAT_IGNORE_FILE: &str = ".dircatignore";
+
+/// Collect matching files, along with the directories pruned by exclusions
+///
+/// When `use_ignore_files` is set, the walk honors `.gitignore` (including nested
+/// files, negations, `core.excludesFile` and `.git/info/exclude`), `.ignore` and
+/// `.dircatignore` files, the same way git and ripgrep do. The walk runs on
+/// `jobs` threads (0 picks one per CPU); results are sorted afterwards, so the
+/// order doesn't depend on which thread found what.
+pub(crate) fn collect_files(
+    base_dir: &Path,
+    include: &GlobSet,
+    exclude: &GlobSet,
+    use_ignore_files: bool,
+    jobs: usize,
+) -> (Vec<SourceFile>, Vec<PathBuf>) {
+    let mut builder = WalkBuilder::new(base_dir);
+    builder
+        .follow_links(false)
+        .hidden(false)
+        .parents(use_ignore_files)
+        .ignore(use_ignore_files)
+        .git_ignore(use_ignore_files)
+        .git_global(use_ignore_files)
+        .git_exclude(use_ignore_files)
+        .threads(jobs);
+    if use_ignore_files {
+        builder.add_custom_ignore_filename(DIRCAT_IGNORE_FILE);
+    }
+
+    let base = base_dir.to_path_buf();
+    let exclude_dirs = exclude.clone();
+    let pruned = Arc::new(Mutex::new(Vec::new()));
+    let pruned_dirs = Arc::clone(&pruned);
+    builder.filter_entry(move |e| {
+        if !e.file_type().is_some_and(|t| t.is_dir()) {
+            return true;
+        }
+        if should_prune_dir(e, &base, &exclude_dirs) {
+            if let Ok(rel_dir) = e.path().strip_prefix(&base) {
+                pruned_dirs.lock().unwrap().push(rel_dir.to_path_buf());
+            }
+            return false;
+        }
+        true
+    });
+
+    let results = Mutex::new(Vec::new());
+    builder.build_parallel().run(|| {
+        Box::new(|entry| {
+            let Ok(entry) = entry else {
+                return WalkState::Continue;
+            };
+            if !entry.file_type().is_some_and(|t| t.is_file()) {
+                return WalkState::Continue;
+            }
+
+            let full_path = entry.path();
+            if let Ok(rel_path) = full_path.strip_prefix(base_dir)
+                && is_selected(rel_path, include, exclude)
+            {
+                results.lock().unwrap().push(SourceFile {
+                    rel_path: rel_path.to_path_buf(),
+                    source: FileSource::Disk(full_path.to_path_buf()),
+                });
+            }
+            WalkState::Continue
+        })
+    });
+
+    let mut results = results.into_inner().unwrap();
+    results.sort_by(|a, b| a.rel_path.cmp(&b.rel_path));
+    let mut pruned = std::mem::take(&mut *pruned.lock().unwrap());
+    pruned.sort();
+    (results, pruned)
+}
+
+/// Collect matching files from the tree of a git revision instead of the working directory
+///
+/// The same hidden-directory pruning and include/exclude patterns apply; ignore
+/// files don't, since everything in a commit is tracked.
+pub(crate) fn collect_rev_files(
+    base_dir: &Path,
+    rev: &str,
+    include: &GlobSet,
+    exclude: &GlobSet,
+) -> Result<(Vec<SourceFile>, Vec<PathBuf>), String> {
+    let repo = git::GitRepo::discover(base_dir)?;
+    let (blobs, commit_time) = repo.snapshot(rev)?;
+    let mut results = Vec::new();
+    let mut pruned_dirs = BTreeSet::new();
+
+    for (repo_path, id) in blobs {
+        let Some(rel_path) = repo.rel_path(&repo_path) else {
+            continue;
+        };
+
+        let mut dirs: Vec<&Path> = rel_path
+            .ancestors()
+            .skip(1)
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .collect();
+        dirs.reverse();
+        if let Some(dir) = dirs.into_iter().find(|dir| is_pruned_dir(dir, exclude)) {
+            pruned_dirs.insert(dir.to_path_buf());
+            continue;
+        }
+        if !is_selected(&rel_path, include, exclude) {
+            continue;
+        }
+
+        results.push(SourceFile {
+            rel_path,
+            source: FileSource::Memory {
+                data: repo.read_blob(id)?.into(),
+                modified: commit_time,
+            },
+        });
+    }
+
+    results.sort_by(|a, b| a.rel_path.cmp(&b.rel_path));
+    Ok((results, pruned_dirs.into_iter().collect()))
+}
+
+/// First line of every Markdown and XML bundle, used to recognize dircat's own output
+pub const BUNDLE_MARKER: &str = "<!-- generated by dircat -->";
+
+/// Value of the `generator` key that leads the first record of JSON and JSON Lines bundles
+pub(crate) const GENERATOR: &str = "dircat";
+
+/// Whether `head`, the start of a file, looks like a bundle dircat wrote
+///
+/// Markdown and XML bundles start with [`BUNDLE_MARKER`]. JSON can't carry a
+/// comment, so JSON and JSON Lines bundles are recognized by the
+/// `"generator":"dircat"` key their first record starts with; a bundle with no
+/// files has a record holding only that key.
+pub fn is_bundle(head: &[u8]) -> bool {
+    let text = String::from_utf8_lossy(head);
+    let text = text.trim_start();
+    if text.starts_with(BUNDLE_MARKER) {
+        return true;
+    }
+    let record = text.strip_prefix('[').unwrap_or(text).trim_start();
+    record
+        .strip_prefix(&format!("{{\"generator\":\"{}\"", GENERATOR))
+        .is_some_and(|rest| rest.starts_with([',', '}']))
+}
+
+/// How much of a file's start `is_bundle` looks at
+pub(crate) const BUNDLE_SNIFF_LEN: usize = 1024;
+
+/// Up to `len` bytes from the start of a collected file; empty if it can't be read
+pub(crate) fn read_head(file: &SourceFile, len: u64) -> Vec<u8> {
+    let mut head = Vec::new();
+    if let Ok(reader) = file.open()
+        && reader.take(len).read_to_end(&mut head).is_err()
+    {
+        head.clear();
+    }
+    head
+}
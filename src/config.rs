@@ -0,0 +1,204 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+/// Name of the project configuration file, searched for upward from the base directory
+pub const CONFIG_FILE: &str = "dircat.toml";
+
+/// A size given either as a byte count or as text with a K/M/G suffix
+#[derive(Deserialize, Clone)]
+#[serde(untagged)]
+pub enum Size {
+    Bytes(u64),
+    Text(String),
+}
+
+/// Bundle options that can be set in a config file or profile
+///
+/// Keys are the long command-line flag names. Everything except `exclude`
+/// replaces the value from earlier layers; exclusions accumulate.
+#[derive(Deserialize, Default, Clone)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Options {
+    pub include: Option<Vec<String>>,
+    pub exclude: Vec<String>,
+    pub output: Option<PathBuf>,
+    pub format: Option<String>,
+    pub no_ignore: Option<bool>,
+    pub jobs: Option<usize>,
+    pub max_tokens: Option<usize>,
+    pub priority: Option<Vec<String>>,
+    pub tokenizer: Option<String>,
+    pub embed_binary: Option<Size>,
+    pub encoding: Option<String>,
+    pub lossy: Option<bool>,
+    pub tree: Option<bool>,
+    pub tree_omitted: Option<bool>,
+    pub toc: Option<bool>,
+}
+
+impl Options {
+    /// Layer `other` on top of these options
+    fn merge(&mut self, other: Options) {
+        self.include = other.include.or(self.include.take());
+        self.output = other.output.or(self.output.take());
+        self.format = other.format.or(self.format.take());
+        self.no_ignore = other.no_ignore.or(self.no_ignore.take());
+        self.jobs = other.jobs.or(self.jobs.take());
+        self.max_tokens = other.max_tokens.or(self.max_tokens.take());
+        self.priority = other.priority.or(self.priority.take());
+        self.tokenizer = other.tokenizer.or(self.tokenizer.take());
+        self.embed_binary = other.embed_binary.or(self.embed_binary.take());
+        self.encoding = other.encoding.or(self.encoding.take());
+        self.lossy = other.lossy.or(self.lossy.take());
+        self.tree = other.tree.or(self.tree.take());
+        self.tree_omitted = other.tree_omitted.or(self.tree_omitted.take());
+        self.toc = other.toc.or(self.toc.take());
+        self.exclude.extend(other.exclude);
+    }
+
+    /// Resolve a relative output path against the directory of the file it came from
+    fn relative_to(mut self, dir: &Path) -> Options {
+        if let Some(output) = self.output.take() {
+            self.output = Some(dir.join(output));
+        }
+        self
+    }
+}
+
+/// One parsed configuration file
+struct ConfigFile {
+    path: PathBuf,
+    options: Options,
+    profiles: BTreeMap<String, Options>,
+}
+
+impl ConfigFile {
+    fn load(path: &Path) -> Result<ConfigFile, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let mut table: toml::Table = text
+            .parse()
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        let profile_table = table.remove("profile");
+
+        let dir = path.parent().unwrap_or(Path::new(""));
+        let parse = |value: toml::Value, what: &str| {
+            value
+                .try_into::<Options>()
+                .map(|o| o.relative_to(dir))
+                .map_err(|e| format!("{}: {}: {}", path.display(), what, e))
+        };
+
+        let options = parse(toml::Value::Table(table), "top level")?;
+        let mut profiles = BTreeMap::new();
+        match profile_table {
+            None => {}
+            Some(toml::Value::Table(entries)) => {
+                for (name, value) in entries {
+                    let options = parse(value, &format!("[profile.{}]", name))?;
+                    profiles.insert(name, options);
+                }
+            }
+            Some(_) => {
+                return Err(format!(
+                    "{}: `profile` must be a table of [profile.<name>] sections",
+                    path.display()
+                ));
+            }
+        }
+
+        Ok(ConfigFile {
+            path: path.to_path_buf(),
+            options,
+            profiles,
+        })
+    }
+}
+
+/// Location of the user-level config file, if a home directory is known
+pub fn user_config_path() -> Option<PathBuf> {
+    let config_dir = env::var_os("XDG_CONFIG_HOME")
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("APPDATA").map(PathBuf::from))
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config_dir.join("dircat").join("config.toml"))
+}
+
+/// Find `dircat.toml` in `base_dir` or the nearest ancestor that has one
+pub fn find_project_config(base_dir: &Path) -> Option<PathBuf> {
+    let start = base_dir.canonicalize().ok()?;
+    start
+        .ancestors()
+        .map(|dir| dir.join(CONFIG_FILE))
+        .find(|path| path.is_file())
+}
+
+/// Settings gathered from the config files, and where they came from
+pub struct Loaded {
+    pub options: Options,
+    /// One description per layer applied, e.g. "config /repo/dircat.toml"
+    pub sources: Vec<String>,
+}
+
+/// Load the user config and the project config, then apply `profile` on top
+///
+/// `project` overrides the discovered `dircat.toml`. The profile is looked up
+/// in the project config first and then in the user config.
+pub fn load(
+    base_dir: &Path,
+    project: Option<&Path>,
+    profile: Option<&str>,
+) -> Result<Loaded, String> {
+    let mut files = Vec::new();
+    if let Some(path) = user_config_path().filter(|p| p.is_file()) {
+        files.push(ConfigFile::load(&path)?);
+    }
+    let project = match project {
+        Some(path) => Some(path.to_path_buf()),
+        None => find_project_config(base_dir),
+    };
+    if let Some(path) = project {
+        files.push(ConfigFile::load(&path)?);
+    }
+
+    let mut loaded = Loaded {
+        options: Options::default(),
+        sources: Vec::new(),
+    };
+    for file in &files {
+        loaded.options.merge(file.options.clone());
+        loaded
+            .sources
+            .push(format!("config {}", file.path.display()));
+    }
+
+    if let Some(name) = profile {
+        let Some(file) = files.iter().rev().find(|f| f.profiles.contains_key(name)) else {
+            let mut known: Vec<&str> = files
+                .iter()
+                .flat_map(|f| f.profiles.keys().map(String::as_str))
+                .collect();
+            known.sort();
+            known.dedup();
+            return Err(if known.is_empty() {
+                format!("unknown profile '{}' (no profiles are defined)", name)
+            } else {
+                format!(
+                    "unknown profile '{}' (available: {})",
+                    name,
+                    known.join(", ")
+                )
+            });
+        };
+        loaded.options.merge(file.profiles[name].clone());
+        loaded
+            .sources
+            .push(format!("profile '{}' from {}", name, file.path.display()));
+    }
+
+    Ok(loaded)
+}
@@ -0,0 +1,244 @@
+use std::io::{self, Read};
+
+use base64::Engine;
+use chardetng::{EncodingDetector, Iso2022JpDetection, Utf8Detection};
+use encoding_rs::{Encoding, UTF_8};
+use sha2::{Digest, Sha256};
+
+use crate::SourceFile;
+
+/// How many leading bytes are inspected to decide whether a file is binary
+const SNIFF_LEN: u64 = 8192;
+
+/// Options controlling how file contents are read
+#[derive(Clone, Default)]
+pub struct ReadOptions {
+    /// Embed binary files up to this many bytes as base64 instead of a placeholder
+    pub embed_binary_max: Option<u64>,
+    /// Decode files without a BOM using this encoding instead of detecting one
+    pub encoding: Option<&'static Encoding>,
+    /// Replace undecodable bytes with U+FFFD instead of failing
+    pub lossy: bool,
+}
+
+/// What a collected file turned out to contain
+pub enum Content {
+    Text(String),
+    Binary {
+        /// Human-readable file type, e.g. "PNG image"
+        kind: &'static str,
+        /// The whole file, base64-encoded, when small enough to embed
+        base64: Option<String>,
+    },
+}
+
+/// A collected file's contents, ready for output
+pub struct FileContent {
+    pub size: u64,
+    /// Lowercase hex SHA-256 of the raw bytes
+    pub sha256: String,
+    pub content: Content,
+    /// Encoding text was decoded from, `None` for binary files
+    pub encoding: Option<&'static Encoding>,
+    /// Whether undecodable bytes were replaced with U+FFFD
+    pub lossy: bool,
+}
+
+impl FileContent {
+    /// One-line description written in place of a binary file's contents
+    pub fn placeholder(&self) -> Option<String> {
+        match &self.content {
+            Content::Text(_) => None,
+            Content::Binary { kind, base64 } => Some(format!(
+                "[Binary file: {}, {} bytes, sha256 {}{}]",
+                kind,
+                self.size,
+                self.sha256,
+                if base64.is_some() {
+                    ", base64-encoded"
+                } else {
+                    ""
+                }
+            )),
+        }
+    }
+}
+
+/// Lowercase hex encoding of a digest
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Magic numbers of common binary formats: offset, signature, description
+const MAGIC: &[(usize, &[u8], &str)] = &[
+    (0, b"\x89PNG\r\n\x1a\n", "PNG image"),
+    (0, b"\xff\xd8\xff", "JPEG image"),
+    (0, b"GIF87a", "GIF image"),
+    (0, b"GIF89a", "GIF image"),
+    (8, b"WEBP", "WebP image"),
+    (0, b"\x00\x00\x01\x00", "ICO image"),
+    (0, b"8BPS", "Photoshop image"),
+    (0, b"%PDF-", "PDF document"),
+    (0, b"PK\x03\x04", "ZIP archive"),
+    (0, b"PK\x05\x06", "ZIP archive"),
+    (0, b"\x1f\x8b", "gzip archive"),
+    (0, b"\xfd7zXZ\x00", "xz archive"),
+    (0, b"7z\xbc\xaf\x27\x1c", "7-Zip archive"),
+    (0, b"\x28\xb5\x2f\xfd", "Zstandard archive"),
+    (4, b"1AY&SY", "bzip2 archive"),
+    (257, b"ustar", "tar archive"),
+    (0, b"Rar!\x1a\x07", "RAR archive"),
+    (0, b"\x7fELF", "ELF executable"),
+    (0, b"MZ", "Windows executable"),
+    (0, b"\xfe\xed\xfa\xce", "Mach-O executable"),
+    (0, b"\xfe\xed\xfa\xcf", "Mach-O executable"),
+    (0, b"\xce\xfa\xed\xfe", "Mach-O executable"),
+    (0, b"\xcf\xfa\xed\xfe", "Mach-O executable"),
+    (0, b"\xca\xfe\xba\xbe", "Java class or universal binary"),
+    (0, b"\x00asm", "WebAssembly module"),
+    (0, b"SQLite format 3\x00", "SQLite database"),
+    (0, b"wOFF", "WOFF font"),
+    (0, b"wOF2", "WOFF2 font"),
+    (0, b"OTTO", "OpenType font"),
+    (0, b"\x00\x01\x00\x00\x00", "TrueType font"),
+    (0, b"ID3", "MP3 audio"),
+    (0, b"OggS", "Ogg media"),
+    (0, b"fLaC", "FLAC audio"),
+    (4, b"ftyp", "MP4 media"),
+];
+
+/// Whether `head` could be the start of a UTF-8 text file
+fn looks_textual(head: &[u8]) -> bool {
+    if head.contains(&0) {
+        return false;
+    }
+    match std::str::from_utf8(head) {
+        Ok(_) => true,
+        // A multi-byte character cut off at the end of the sniffed range is fine
+        Err(e) => e.error_len().is_none(),
+    }
+}
+
+/// Identify binary content from its first bytes
+///
+/// Known magic numbers give a specific type; otherwise any NUL byte marks the
+/// file as generic binary data, the same heuristic git and grep use.
+pub fn sniff_binary(head: &[u8]) -> Option<&'static str> {
+    for &(offset, signature, kind) in MAGIC {
+        if head.get(offset..offset + signature.len()) != Some(signature) {
+            continue;
+        }
+        // Signatures made of printable ASCII ("MZ", "ID3", "%PDF-") can also
+        // start a text file, so only trust them when the rest isn't text
+        let printable = signature.iter().all(|b| b.is_ascii_graphic());
+        if printable && looks_textual(head) {
+            continue;
+        }
+        return Some(kind);
+    }
+
+    if head.contains(&0) {
+        Some("binary data")
+    } else {
+        None
+    }
+}
+
+/// Read a collected file, detecting binary content before loading it all
+///
+/// Binary files are hashed as they stream past and only kept in memory when
+/// they are small enough to embed.
+pub fn read_content(file: &SourceFile, options: &ReadOptions) -> io::Result<FileContent> {
+    let mut reader = file.open()?;
+    let mut bytes = Vec::new();
+    (&mut reader).take(SNIFF_LEN).read_to_end(&mut bytes)?;
+
+    // UTF-16 text is full of NUL bytes, so a BOM has to be checked first
+    let binary = match Encoding::for_bom(&bytes) {
+        Some(_) => None,
+        None => sniff_binary(&bytes),
+    };
+
+    if let Some(kind) = binary {
+        let mut hasher = Sha256::new();
+        let mut size = bytes.len() as u64;
+        hasher.update(&bytes);
+
+        let mut keep = options.embed_binary_max.is_some_and(|max| size <= max);
+        let mut buf = [0u8; 64 * 1024];
+        loop {
+            let n = reader.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            hasher.update(&buf[..n]);
+            size += n as u64;
+            keep = keep && options.embed_binary_max.is_some_and(|max| size <= max);
+            if keep {
+                bytes.extend_from_slice(&buf[..n]);
+            }
+        }
+
+        let base64 = keep.then(|| base64::engine::general_purpose::STANDARD.encode(&bytes));
+        return Ok(FileContent {
+            size,
+            sha256: hex(&hasher.finalize()),
+            content: Content::Binary { kind, base64 },
+            encoding: None,
+            lossy: false,
+        });
+    }
+
+    reader.read_to_end(&mut bytes)?;
+    let decoded = decode_text(&bytes, options)?;
+    Ok(FileContent {
+        size: bytes.len() as u64,
+        sha256: hex(&Sha256::digest(&bytes)),
+        content: Content::Text(decoded.text),
+        encoding: Some(decoded.encoding),
+        lossy: decoded.lossy,
+    })
+}
+
+/// Text decoded from a file and the encoding it was decoded from
+pub struct Decoded {
+    pub text: String,
+    pub encoding: &'static Encoding,
+    pub lossy: bool,
+}
+
+/// Guess the legacy encoding of bytes that aren't valid UTF-8
+fn detect_encoding(bytes: &[u8]) -> &'static Encoding {
+    let mut detector = EncodingDetector::new(Iso2022JpDetection::Deny);
+    detector.feed(bytes, true);
+    detector.guess(None, Utf8Detection::Deny)
+}
+
+/// Turn raw file bytes into text
+///
+/// A byte order mark always wins; otherwise the forced encoding is used, then
+/// UTF-8 if the bytes are valid, then whichever legacy encoding fits best.
+pub fn decode_text(bytes: &[u8], options: &ReadOptions) -> io::Result<Decoded> {
+    let (encoding, bom_len) = match Encoding::for_bom(bytes) {
+        Some(found) => found,
+        None => match options.encoding {
+            Some(encoding) => (encoding, 0),
+            None if std::str::from_utf8(bytes).is_ok() => (UTF_8, 0),
+            None => (detect_encoding(bytes), 0),
+        },
+    };
+
+    let (text, had_errors) = encoding.decode_without_bom_handling(&bytes[bom_len..]);
+    if had_errors && !options.lossy {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("stream did not contain valid {}", encoding.name()),
+        ));
+    }
+
+    Ok(Decoded {
+        text: text.into_owned(),
+        encoding,
+        lossy: had_errors,
+    })
+}
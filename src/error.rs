@@ -0,0 +1,44 @@
+use std::fmt;
+use std::path::PathBuf;
+
+/// Everything that can stop a bundle from being collected
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// The base directory doesn't exist or isn't a directory
+    NotADirectory(PathBuf),
+    /// No include patterns were given, so nothing would be selected
+    NoIncludePatterns,
+    /// A glob pattern didn't parse; `kind` is "include", "exclude" or "priority"
+    InvalidPattern {
+        kind: &'static str,
+        source: globset::Error,
+    },
+    /// Opening the repository or resolving a revision failed
+    Git(String),
+    /// The thread pool for reading files couldn't be started
+    Threads(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::NotADirectory(path) => write!(f, "{} is not a directory", path.display()),
+            Error::NoIncludePatterns => write!(f, "no include patterns specified"),
+            Error::InvalidPattern { kind, source } => {
+                write!(f, "invalid {} pattern: {}", kind, source)
+            }
+            Error::Git(message) => write!(f, "{}", message),
+            Error::Threads(message) => write!(f, "couldn't start worker threads: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::InvalidPattern { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,392 @@
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use encoding_rs::Encoding;
+use gix::ObjectId;
+
+use crate::SourceFile;
+use crate::content::{ReadOptions, decode_text, sniff_binary};
+
+/// Which changes to restrict the bundle to
+#[derive(Clone, Debug)]
+pub enum ChangeSelection {
+    /// Working tree (including staged and untracked files) versus a revision
+    ChangedSince(String),
+    /// Index versus `HEAD`
+    Staged,
+    /// Working tree and index versus `HEAD`
+    Uncommitted,
+    /// `A..B` tree difference, or a single commit versus its first parent
+    Commits(String),
+}
+
+/// A local repository opened from somewhere inside its working tree
+pub struct GitRepo {
+    repo: gix::Repository,
+    /// The scanned directory, relative to the working tree root
+    prefix: PathBuf,
+}
+
+impl GitRepo {
+    /// Discover the repository containing `base_dir`
+    pub fn discover(base_dir: &Path) -> Result<GitRepo, String> {
+        let repo = gix::discover(base_dir).map_err(|e| {
+            format!(
+                "{} is not inside a git repository: {}",
+                base_dir.display(),
+                e
+            )
+        })?;
+        let workdir = repo
+            .workdir()
+            .ok_or_else(|| "repository has no working tree".to_string())?;
+
+        let canonical_base = base_dir
+            .canonicalize()
+            .map_err(|e| format!("{}: {}", base_dir.display(), e))?;
+        let canonical_workdir = workdir
+            .canonicalize()
+            .map_err(|e| format!("{}: {}", workdir.display(), e))?;
+        let prefix = canonical_base
+            .strip_prefix(&canonical_workdir)
+            .map_err(|_| format!("{} is outside the working tree", base_dir.display()))?
+            .to_path_buf();
+
+        Ok(GitRepo { repo, prefix })
+    }
+
+    /// Repository-relative path (with `/` separators) of a path relative to the scanned directory
+    pub fn repo_path(&self, rel_path: &Path) -> String {
+        let joined = self.prefix.join(rel_path);
+        let mut out = String::new();
+        for component in joined.components() {
+            if !out.is_empty() {
+                out.push('/');
+            }
+            out.push_str(&component.as_os_str().to_string_lossy());
+        }
+        out
+    }
+
+    /// Path relative to the scanned directory for a repository-relative path,
+    /// or `None` if it lies outside of it
+    pub fn rel_path(&self, repo_path: &str) -> Option<PathBuf> {
+        Path::new(repo_path)
+            .strip_prefix(&self.prefix)
+            .ok()
+            .map(Path::to_path_buf)
+    }
+
+    fn commit(&self, rev: &str) -> Result<gix::Commit<'_>, String> {
+        self.repo
+            .rev_parse_single(rev)
+            .map_err(|e| format!("cannot resolve '{}': {}", rev, e))?
+            .object()
+            .map_err(|e| e.to_string())?
+            .peel_to_commit()
+            .map_err(|e| format!("'{}' is not a commit: {}", rev, e))
+    }
+
+    /// Blob ids of every file in the tree of `rev`, keyed by repository-relative path
+    pub fn tree_blobs(&self, rev: &str) -> Result<BTreeMap<String, ObjectId>, String> {
+        let tree = self
+            .commit(rev)?
+            .tree()
+            .map_err(|e| format!("cannot read tree of '{}': {}", rev, e))?;
+        self.blobs_of(&tree)
+    }
+
+    /// Blob ids of every file in the tree of `rev`, plus the commit time to use as
+    /// their modification time
+    pub fn snapshot(
+        &self,
+        rev: &str,
+    ) -> Result<(BTreeMap<String, ObjectId>, Option<SystemTime>), String> {
+        let commit = self.commit(rev)?;
+        let time = commit
+            .time()
+            .ok()
+            .and_then(|t| u64::try_from(t.seconds).ok())
+            .map(|secs| SystemTime::UNIX_EPOCH + Duration::from_secs(secs));
+        let tree = commit
+            .tree()
+            .map_err(|e| format!("cannot read tree of '{}': {}", rev, e))?;
+        Ok((self.blobs_of(&tree)?, time))
+    }
+
+    fn blobs_of(&self, tree: &gix::Tree<'_>) -> Result<BTreeMap<String, ObjectId>, String> {
+        let mut recorder = gix::traverse::tree::Recorder::default();
+        tree.traverse()
+            .breadthfirst(&mut recorder)
+            .map_err(|e| format!("cannot traverse tree: {}", e))?;
+        Ok(recorder
+            .records
+            .into_iter()
+            .filter(|entry| entry.mode.is_blob())
+            .map(|entry| (entry.filepath.to_string(), entry.oid))
+            .collect())
+    }
+
+    /// Contents of the blob `id`
+    pub fn read_blob(&self, id: ObjectId) -> Result<Vec<u8>, String> {
+        self.repo
+            .find_object(id)
+            .map(|object| object.detach().data)
+            .map_err(|e| format!("cannot read blob {}: {}", id, e))
+    }
+
+    /// Blob id that `bytes` would have if committed
+    fn blob_id(&self, bytes: &[u8]) -> Option<ObjectId> {
+        gix::objs::compute_hash(self.repo.object_hash(), gix::object::Kind::Blob, bytes).ok()
+    }
+
+    /// Paths whose index entry differs from `tree`
+    fn index_changes(&self, tree: &BTreeMap<String, ObjectId>) -> Result<HashSet<String>, String> {
+        let index = self
+            .repo
+            .index_or_empty()
+            .map_err(|e| format!("cannot read index: {}", e))?;
+        let mut changed = HashSet::new();
+        for entry in index.entries() {
+            let path = entry.path(&index).to_string();
+            if tree.get(&path) != Some(&entry.id) {
+                changed.insert(path);
+            }
+        }
+        Ok(changed)
+    }
+
+    /// Paths that differ between the trees of two revisions
+    fn tree_changes(&self, from: &str, to: &str) -> Result<HashSet<String>, String> {
+        let old = self.tree_blobs(from)?;
+        let new = self.tree_blobs(to)?;
+        Ok(changed_between(&old, &new))
+    }
+
+    /// Paths changed by a single commit relative to its first parent
+    fn commit_changes(&self, rev: &str) -> Result<HashSet<String>, String> {
+        let commit = self.commit(rev)?;
+        let new = self.blobs_of(&commit.tree().map_err(|e| e.to_string())?)?;
+        let old = match commit.parent_ids().next() {
+            Some(parent) => self.tree_blobs(&parent.to_string())?,
+            None => BTreeMap::new(),
+        };
+        Ok(changed_between(&old, &new))
+    }
+}
+
+fn changed_between(
+    old: &BTreeMap<String, ObjectId>,
+    new: &BTreeMap<String, ObjectId>,
+) -> HashSet<String> {
+    let mut changed: HashSet<String> = new
+        .iter()
+        .filter(|(path, id)| old.get(*path) != Some(id))
+        .map(|(path, _)| path.clone())
+        .collect();
+    changed.extend(old.keys().filter(|path| !new.contains_key(*path)).cloned());
+    changed
+}
+
+/// Decides whether a collected file is part of the selected changes
+pub struct ChangeFilter {
+    repo: GitRepo,
+    /// Paths known to be changed
+    paths: HashSet<String>,
+    /// Trees to compare working tree files against
+    worktree_bases: Vec<BTreeMap<String, ObjectId>>,
+}
+
+impl ChangeFilter {
+    /// Resolve every selection against the repository containing `base_dir`;
+    /// a file matches if it is part of any of them
+    pub fn new(base_dir: &Path, selections: &[ChangeSelection]) -> Result<ChangeFilter, String> {
+        let repo = GitRepo::discover(base_dir)?;
+        let mut paths = HashSet::new();
+        let mut worktree_bases = Vec::new();
+
+        for selection in selections {
+            match selection {
+                ChangeSelection::ChangedSince(rev) => {
+                    let tree = repo.tree_blobs(rev)?;
+                    paths.extend(repo.index_changes(&tree)?);
+                    worktree_bases.push(tree);
+                }
+                ChangeSelection::Staged => {
+                    paths.extend(repo.index_changes(&repo.tree_blobs("HEAD")?)?);
+                }
+                ChangeSelection::Uncommitted => {
+                    let tree = repo.tree_blobs("HEAD")?;
+                    paths.extend(repo.index_changes(&tree)?);
+                    worktree_bases.push(tree);
+                }
+                ChangeSelection::Commits(range) => match range.split_once("..") {
+                    Some((from, to)) => {
+                        if to.starts_with('.') {
+                            return Err(format!(
+                                "'{}': only A..B ranges are supported, not A...B",
+                                range
+                            ));
+                        }
+                        let from = if from.is_empty() { "HEAD" } else { from };
+                        let to = if to.is_empty() { "HEAD" } else { to };
+                        paths.extend(repo.tree_changes(from, to)?);
+                    }
+                    None => paths.extend(repo.commit_changes(range)?),
+                },
+            }
+        }
+
+        Ok(ChangeFilter {
+            repo,
+            paths,
+            worktree_bases,
+        })
+    }
+
+    /// Whether a collected file is part of the selected changes
+    pub fn matches(&self, file: &SourceFile) -> bool {
+        let repo_path = self.repo.repo_path(&file.rel_path);
+        if self.paths.contains(&repo_path) {
+            return true;
+        }
+        if self.worktree_bases.is_empty() {
+            return false;
+        }
+
+        let Some(id) = file
+            .read_bytes()
+            .ok()
+            .and_then(|bytes| self.repo.blob_id(&bytes))
+        else {
+            return true;
+        };
+        self.worktree_bases
+            .iter()
+            .any(|tree| tree.get(&repo_path) != Some(&id))
+    }
+}
+
+/// Diffs of the bundled files against a base revision
+pub struct PendingChanges {
+    /// The revision the diffs are against, as given on the command line
+    pub base: String,
+    pub diffs: Vec<FileDiff>,
+}
+
+impl PendingChanges {
+    /// The diff for a file, if it changed
+    pub fn diff_for(&self, rel_path: &Path) -> Option<&FileDiff> {
+        self.diffs.iter().find(|d| d.rel_path == rel_path)
+    }
+}
+
+/// Unified diff of one file against the diff base
+pub struct FileDiff {
+    /// Path relative to the scanned directory
+    pub rel_path: PathBuf,
+    pub patch: String,
+    /// Whether the file is gone from the working directory, so it has no
+    /// section of its own in the bundle
+    pub deleted: bool,
+}
+
+/// Unified diff between two versions of a file, `None` meaning the file doesn't exist
+fn unified_diff(repo_path: &str, old: Option<&[u8]>, new: Option<&[u8]>) -> String {
+    let decode = |bytes: Option<&[u8]>| match bytes {
+        Some(b) if Encoding::for_bom(b).is_none() && sniff_binary(b).is_some() => None,
+        Some(b) => decode_text(b, &ReadOptions::default()).ok().map(|d| d.text),
+        None => Some(String::new()),
+    };
+    let (Some(old_text), Some(new_text)) = (decode(old), decode(new)) else {
+        return format!("Binary files a/{} and b/{} differ\n", repo_path, repo_path);
+    };
+
+    let original = match old {
+        Some(_) => format!("a/{}", repo_path),
+        None => "/dev/null".to_string(),
+    };
+    let modified = match new {
+        Some(_) => format!("b/{}", repo_path),
+        None => "/dev/null".to_string(),
+    };
+    diffy::DiffOptions::new()
+        .set_original_filename(original)
+        .set_modified_filename(modified)
+        .create_patch(&old_text, &new_text)
+        .to_string()
+}
+
+/// Diff every collected file against the tree of `base_rev`
+///
+/// Files that exist in the base but are gone from the working directory are
+/// reported as deletions too. Any file with contents in the base is only
+/// diffed if `keep_diff`, given the path and those contents, agrees; for a
+/// deleted file it also decides whether the file would have been collected.
+pub fn pending_changes(
+    base_dir: &Path,
+    base_rev: &str,
+    files: &[SourceFile],
+    keep_diff: &mut dyn FnMut(&Path, &[u8]) -> bool,
+) -> Result<PendingChanges, String> {
+    let repo = GitRepo::discover(base_dir)?;
+    let tree = repo.tree_blobs(base_rev)?;
+    let mut diffs = Vec::new();
+    let mut seen = HashSet::new();
+
+    for file in files {
+        let repo_path = repo.repo_path(&file.rel_path);
+        seen.insert(repo_path.clone());
+
+        // The writers report an unreadable file in its own section
+        let Ok(new) = file.read_bytes() else {
+            continue;
+        };
+        let old = match tree.get(&repo_path) {
+            Some(id) => Some(repo.read_blob(*id)?),
+            None => None,
+        };
+        if old.as_deref() == Some(new.as_slice()) {
+            continue;
+        }
+        if let Some(old) = &old
+            && !keep_diff(&file.rel_path, old)
+        {
+            continue;
+        }
+
+        diffs.push(FileDiff {
+            rel_path: file.rel_path.clone(),
+            patch: unified_diff(&repo_path, old.as_deref(), Some(&new)),
+            deleted: false,
+        });
+    }
+
+    for (repo_path, id) in &tree {
+        if seen.contains(repo_path) {
+            continue;
+        }
+        let Some(rel_path) = repo.rel_path(repo_path) else {
+            continue;
+        };
+        if base_dir.join(&rel_path).exists() {
+            continue;
+        }
+        let old = repo.read_blob(*id)?;
+        if !keep_diff(&rel_path, &old) {
+            continue;
+        }
+        diffs.push(FileDiff {
+            patch: unified_diff(repo_path, Some(&old), None),
+            rel_path,
+            deleted: true,
+        });
+    }
+
+    diffs.sort_by(|a, b| a.rel_path.cmp(&b.rel_path));
+    Ok(PendingChanges {
+        base: base_rev.to_string(),
+        diffs,
+    })
+}
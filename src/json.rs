@@ -0,0 +1,173 @@
+use std::io::{self, Write};
+
+use serde::Serialize;
+
+use crate::collect::GENERATOR;
+use crate::content::FileContent;
+use crate::git::FileDiff;
+use crate::record::{DeletedRecord, file_record};
+use crate::writer::BundleWriter;
+use crate::{Bundle, Format, SourceFile};
+
+/// The first record of a bundle, led by a `generator` key that marks the
+/// output as dircat's
+#[derive(Serialize)]
+struct FirstRecord<'a, T> {
+    generator: &'static str,
+    #[serde(flatten)]
+    record: &'a T,
+}
+
+/// Serialize a record, marking it if it is the first of the bundle
+fn write_record<W: Write, T: Serialize>(writer: &mut W, record: &T, first: bool) -> io::Result<()> {
+    if first {
+        let record = FirstRecord {
+            generator: GENERATOR,
+            record,
+        };
+        serde_json::to_writer(writer, &record)?;
+    } else {
+        serde_json::to_writer(writer, record)?;
+    }
+    Ok(())
+}
+
+/// Stands in for the records of a bundle that has none, so it still carries
+/// the generator key
+#[derive(Serialize)]
+struct NoRecords {}
+
+/// Diffs of files that are gone, which have no file record to carry them
+fn deleted_diffs(bundle: &Bundle) -> impl Iterator<Item = &FileDiff> {
+    bundle
+        .changes
+        .iter()
+        .flat_map(|c| &c.diffs)
+        .filter(|d| d.deleted)
+}
+
+/// Write the record of a deleted file's diff, the `index`th record of the
+/// bundle, the way `format` lays out records
+pub(crate) fn write_deleted<W: Write>(
+    writer: &mut W,
+    format: Format,
+    index: usize,
+    diff: &FileDiff,
+) -> io::Result<()> {
+    let record = DeletedRecord::new(diff);
+    match format {
+        Format::JsonLines => JsonLinesWriter::new(writer).record(index, &record),
+        _ => JsonWriter::new(writer).record(index, &record),
+    }
+}
+
+/// Writes a JSON array with one record per file
+///
+/// Commas and the generator key are placed by each record's index, so a
+/// record renders the same whether or not the ones before it were written.
+pub struct JsonWriter<W> {
+    writer: W,
+}
+
+impl<W: Write> JsonWriter<W> {
+    pub fn new(writer: W) -> JsonWriter<W> {
+        JsonWriter { writer }
+    }
+
+    fn record<T: Serialize>(&mut self, index: usize, record: &T) -> io::Result<()> {
+        if index > 0 {
+            writeln!(self.writer, ",")?;
+        }
+        write_record(&mut self.writer, record, index == 0)
+    }
+}
+
+impl<W: Write> BundleWriter for JsonWriter<W> {
+    fn begin(&mut self, _bundle: &Bundle) -> io::Result<()> {
+        writeln!(self.writer, "[")
+    }
+
+    fn file(
+        &mut self,
+        bundle: &Bundle,
+        index: usize,
+        file: &SourceFile,
+        content: &FileContent,
+    ) -> io::Result<()> {
+        self.record(index, &file_record(bundle, file, Ok(content)))
+    }
+
+    fn read_error(
+        &mut self,
+        bundle: &Bundle,
+        index: usize,
+        file: &SourceFile,
+        error: &io::Error,
+    ) -> io::Result<()> {
+        self.record(index, &file_record(bundle, file, Err(error)))
+    }
+
+    fn end(&mut self, bundle: &Bundle) -> io::Result<()> {
+        let mut index = bundle.files.len();
+        for diff in deleted_diffs(bundle) {
+            self.record(index, &DeletedRecord::new(diff))?;
+            index += 1;
+        }
+        if index == 0 {
+            self.record(index, &NoRecords {})?;
+        }
+        writeln!(self.writer)?;
+        writeln!(self.writer, "]")?;
+        self.writer.flush()
+    }
+}
+
+/// Writes JSON Lines, one record per file
+pub struct JsonLinesWriter<W> {
+    writer: W,
+}
+
+impl<W: Write> JsonLinesWriter<W> {
+    pub fn new(writer: W) -> JsonLinesWriter<W> {
+        JsonLinesWriter { writer }
+    }
+
+    fn record<T: Serialize>(&mut self, index: usize, record: &T) -> io::Result<()> {
+        write_record(&mut self.writer, record, index == 0)?;
+        writeln!(self.writer)
+    }
+}
+
+impl<W: Write> BundleWriter for JsonLinesWriter<W> {
+    fn file(
+        &mut self,
+        bundle: &Bundle,
+        index: usize,
+        file: &SourceFile,
+        content: &FileContent,
+    ) -> io::Result<()> {
+        self.record(index, &file_record(bundle, file, Ok(content)))
+    }
+
+    fn read_error(
+        &mut self,
+        bundle: &Bundle,
+        index: usize,
+        file: &SourceFile,
+        error: &io::Error,
+    ) -> io::Result<()> {
+        self.record(index, &file_record(bundle, file, Err(error)))
+    }
+
+    fn end(&mut self, bundle: &Bundle) -> io::Result<()> {
+        let mut index = bundle.files.len();
+        for diff in deleted_diffs(bundle) {
+            self.record(index, &DeletedRecord::new(diff))?;
+            index += 1;
+        }
+        if index == 0 {
+            self.record(index, &NoRecords {})?;
+        }
+        self.writer.flush()
+    }
+}
@@ -0,0 +1,53 @@
+use std::path::Path;
+
+/// Maps file extensions to Markdown code block language hints
+pub fn get_language_hint(path: &Path) -> &'static str {
+    match path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|s| s.to_lowercase())
+    {
+        Some(ext) => match ext.as_str() {
+            "py" => "python",
+            "js" => "javascript",
+            "ts" => "typescript",
+            "jsx" => "jsx",
+            "tsx" => "tsx",
+            "java" => "java",
+            "c" => "c",
+            "cpp" => "cpp",
+            "cs" => "csharp",
+            "php" => "php",
+            "rb" => "ruby",
+            "go" => "go",
+            "rs" => "rust",
+            "kt" => "kotlin",
+            "swift" => "swift",
+            "m" => "objectivec",
+            "scala" => "scala",
+            "sh" => "bash",
+            "bash" => "bash",
+            "zsh" => "zsh",
+            "fish" => "fish",
+            "ps1" => "powershell",
+            "r" => "r",
+            "sql" => "sql",
+            "html" | "htm" => "html",
+            "xml" => "xml",
+            "css" => "css",
+            "scss" => "scss",
+            "sass" => "sass",
+            "less" => "less",
+            "json" => "json",
+            "yaml" | "yml" => "yaml",
+            "toml" => "toml",
+            "ini" | "cfg" => "ini",
+            "conf" => "conf",
+            "md" | "markdown" => "markdown",
+            "rst" => "rst",
+            "tex" => "latex",
+            _ => "",
+        },
+        None => "",
+    }
+}
@@ -0,0 +1,53 @@
+//! Concatenate source files into a single Markdown, XML or JSON bundle for LLMs
+//!
+//! A [`Bundler`] selects files the same way the `dircat` command does, and
+//! [`Bundler::collect`] returns a [`Bundle`] that can be written out or read
+//! file by file as [`FileRecord`]s. Each output format is a [`BundleWriter`],
+//! and [`Bundle::write_to`] renders several of them while reading every file once.
+//!
+//! ```no_run
+//! use dircat::{Bundler, Format};
+//!
+//! let bundle = Bundler::new("src")
+//!     .include(["*.rs"])
+//!     .exclude(["generated"])
+//!     .max_tokens(50_000)
+//!     .format(Format::Xml)
+//!     .collect()?;
+//!
+//! for record in bundle.records() {
+//!     println!("{} ({} bytes)", record.path, record.size);
+//! }
+//! bundle.write(&mut std::io::stdout())?;
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+mod bundle;
+mod collect;
+mod content;
+mod error;
+mod git;
+mod json;
+mod language;
+mod markdown;
+mod record;
+mod sensitive;
+mod tokens;
+mod tree;
+mod writer;
+mod xml;
+
+pub use bundle::{Bundle, Bundler, Format, Notice, OutputTarget, TokenReport};
+pub use collect::{BUNDLE_MARKER, SourceFile, is_bundle};
+pub use content::{Content, FileContent};
+pub use encoding_rs::Encoding;
+pub use error::Error;
+pub use git::{ChangeSelection, FileDiff, PendingChanges};
+pub use json::{JsonLinesWriter, JsonWriter};
+pub use language::get_language_hint;
+pub use markdown::MarkdownWriter;
+pub use record::FileRecord;
+pub use tokens::Tokenizer;
+pub use tree::format_size;
+pub use writer::BundleWriter;
+pub use xml::XmlWriter;
@@ -1,265 +1,750 @@
-use std::env;
 use std::fs::{self, File};
-use std::io::{BufWriter, Write};
-use std::path::{Path, PathBuf};
-
-use globset::{Glob, GlobSet, GlobSetBuilder};
-use walkdir::{DirEntry, WalkDir};
-
-/// Maps file extensions to Markdown code block language hints
-fn get_language_hint(path: &Path) -> &'static str {
-    match path
-        .extension()
-        .and_then(|e| e.to_str())
-        .map(|s| s.to_lowercase())
-    {
-        Some(ext) => match ext.as_str() {
-            "py" => "python",
-            "js" => "javascript",
-            "ts" => "typescript",
-            "jsx" => "jsx",
-            "tsx" => "tsx",
-            "java" => "java",
-            "c" => "c",
-            "cpp" => "cpp",
-            "cs" => "csharp",
-            "php" => "php",
-            "rb" => "ruby",
-            "go" => "go",
-            "rs" => "rust",
-            "kt" => "kotlin",
-            "swift" => "swift",
-            "m" => "objectivec",
-            "scala" => "scala",
-            "sh" => "bash",
-            "bash" => "bash",
-            "zsh" => "zsh",
-            "fish" => "fish",
-            "ps1" => "powershell",
-            "r" => "r",
-            "sql" => "sql",
-            "html" | "htm" => "html",
-            "xml" => "xml",
-            "css" => "css",
-            "scss" => "scss",
-            "sass" => "sass",
-            "less" => "less",
-            "json" => "json",
-            "yaml" | "yml" => "yaml",
-            "toml" => "toml",
-            "ini" | "cfg" => "ini",
-            "conf" => "conf",
-            "md" | "markdown" => "markdown",
-            "rst" => "rst",
-            "tex" => "latex",
-            _ => "",
+use std::io::{self, BufWriter, IsTerminal, Read, Write};
+use std::path::PathBuf;
+
+use clap::CommandFactory;
+use dircat::{
+    Bundle, BundleWriter, Bundler, ChangeSelection, Error, Format, OutputTarget, Tokenizer,
+};
+
+mod apply;
+mod cli;
+mod config;
+mod mcp;
+mod serve;
+mod unpack;
+mod watch;
+
+/// Parse a byte count with an optional K/M/G suffix (powers of 1024)
+fn parse_size(text: &str) -> Option<u64> {
+    let text = text.trim();
+    let (digits, multiplier) = match text.to_ascii_lowercase().trim_end_matches(['b', 'i']) {
+        t if t.ends_with('k') => (text[..t.len() - 1].to_string(), 1 << 10),
+        t if t.ends_with('m') => (text[..t.len() - 1].to_string(), 1 << 20),
+        t if t.ends_with('g') => (text[..t.len() - 1].to_string(), 1 << 30),
+        t => (text[..t.len()].to_string(), 1),
+    };
+    digits.trim().parse::<u64>().ok()?.checked_mul(multiplier)
+}
+
+/// `dircat unpack <bundle> [--dir <target>] [--dry-run] [--diff]`
+fn unpack_main(args: cli::UnpackArgs) {
+    let bundle = args.bundle;
+    let target = args.target;
+    let options = unpack::UnpackOptions {
+        dry_run: args.dry_run,
+        diff: args.diff,
+    };
+
+    let text = if bundle == "-" {
+        io::read_to_string(io::stdin())
+    } else {
+        fs::read_to_string(&bundle)
+    };
+    let text = match text {
+        Ok(t) => t,
+        Err(e) => {
+            eprintln!("Error reading bundle '{}': {}", bundle, e);
+            std::process::exit(1);
+        }
+    };
+
+    let files = match unpack::parse_bundle(&text) {
+        Ok(f) => f,
+        Err(e) => {
+            eprintln!("Error parsing bundle '{}': {}", bundle, e);
+            std::process::exit(1);
+        }
+    };
+
+    let summary = match unpack::unpack(&files, &target, &options) {
+        Ok(s) => s,
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => std::process::exit(0),
+        Err(e) => {
+            eprintln!("Error writing to '{}': {}", target.display(), e);
+            std::process::exit(1);
+        }
+    };
+
+    eprintln!(
+        "{} {} created, {} updated, {} unchanged, {} rejected",
+        if options.dry_run {
+            "Dry run:"
+        } else {
+            "Unpacked:"
         },
-        None => "",
+        summary.created,
+        summary.updated,
+        summary.unchanged,
+        summary.rejected
+    );
+    if summary.rejected > 0 {
+        std::process::exit(1);
     }
 }
 
-/// Build a GlobSet from string patterns
-fn build_globset(patterns: &[String]) -> Result<GlobSet, globset::Error> {
-    let mut builder = GlobSetBuilder::new();
-    for pat in patterns {
-        builder.add(Glob::new(pat)?);
+/// `dircat apply <response> [--dir <base>] [--dry-run]`
+fn apply_main(args: cli::ApplyArgs) {
+    let response = args.response;
+    let base_dir = args.base_dir;
+    let options = apply::ApplyOptions {
+        dry_run: args.dry_run,
+    };
+
+    if !base_dir.is_dir() {
+        eprintln!("Error: {} is not a directory", base_dir.display());
+        std::process::exit(1);
     }
-    builder.build()
-}
 
-/// Decide whether to prune a directory (skip recursion)
-fn should_prune_dir(entry: &DirEntry, base: &Path, exclude: &GlobSet) -> bool {
-    let name = entry.file_name();
+    let text = if response == "-" {
+        io::read_to_string(io::stdin())
+    } else {
+        fs::read_to_string(&response)
+    };
+    let text = match text {
+        Ok(t) => t,
+        Err(e) => {
+            eprintln!("Error reading response '{}': {}", response, e);
+            std::process::exit(1);
+        }
+    };
+
+    let report = match apply::apply(&text, &base_dir, &options) {
+        Ok(r) => r,
+        Err(e) => {
+            eprintln!("Error applying edits: {}", e);
+            std::process::exit(1);
+        }
+    };
 
-    // Don't prune the base directory itself
-    if entry.path() == base {
-        return false;
+    if report.edits == 0 {
+        eprintln!("No edits found in '{}'", response);
+        std::process::exit(1);
+    }
+    if report.failures > 0 {
+        eprintln!(
+            "{} edit(s) failed to apply; no files were changed",
+            report.failures
+        );
+        std::process::exit(1);
     }
+    eprintln!(
+        "{} {} edit(s) to {} file(s)",
+        if options.dry_run {
+            "Dry run: would apply"
+        } else {
+            "Applied"
+        },
+        report.edits,
+        report.files_changed
+    );
+}
 
-    // Skip dot-directories (hidden directories)
-    if name.to_string_lossy().starts_with('.') {
-        return true;
+/// Load the config files (and profile) that apply to a run over `select.directory`
+fn load_settings(select: &cli::SelectArgs) -> config::Options {
+    if select.no_config {
+        return config::Options::default();
     }
+    match config::load(
+        &select.directory,
+        select.config.as_deref(),
+        select.profile.as_deref(),
+    ) {
+        Ok(loaded) => {
+            for source in &loaded.sources {
+                eprintln!("Using {}", source);
+            }
+            loaded.options
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
 
-    let full_path = entry.path();
+/// Include patterns from the command line, or the configured ones
+fn include_patterns(select: &cli::SelectArgs, settings: &config::Options) -> Vec<String> {
+    match (&select.patterns, &settings.include) {
+        (Some(patterns), _) => patterns.split(',').map(str::to_string).collect(),
+        (None, Some(patterns)) => patterns.clone(),
+        (None, None) => Vec::new(),
+    }
+}
 
-    let rel_path = match full_path.strip_prefix(base) {
-        Ok(p) => p,
-        Err(_) => return false,
-    };
+/// Set up a `Bundler` for the files `select` chooses, on top of the configured defaults
+fn bundler(select: &cli::SelectArgs, settings: &config::Options) -> Bundler {
+    let mut bundler = selection(select, settings);
+    bundler.include(include_patterns(select, settings));
+    bundler
+}
 
-    let dot_rel = PathBuf::from(".").join(rel_path);
+/// Like [`bundler`], but without include patterns, for callers that pick their own
+fn selection(select: &cli::SelectArgs, settings: &config::Options) -> Bundler {
+    let mut bundler = Bundler::new(&select.directory);
+    bundler
+        .exclude(settings.exclude.iter().cloned())
+        .exclude(select.exclude.iter().cloned())
+        .ignore_files(!(select.no_ignore || settings.no_ignore.unwrap_or(false)))
+        .jobs(select.jobs.or(settings.jobs).unwrap_or(0))
+        .allow_sensitive(select.allow_sensitive)
+        .on_notice(|notice| eprintln!("{}", notice));
+    if let Some(rev) = &select.rev {
+        bundler.rev(rev.clone());
+    }
 
-    exclude.is_match(name) || exclude.is_match(rel_path) || exclude.is_match(&dot_rel)
+    for rev in &select.changed_since {
+        bundler.select_changes(ChangeSelection::ChangedSince(rev.clone()));
+    }
+    for range in &select.commits {
+        bundler.select_changes(ChangeSelection::Commits(range.clone()));
+    }
+    if select.staged {
+        bundler.select_changes(ChangeSelection::Staged);
+    }
+    if select.uncommitted {
+        bundler.select_changes(ChangeSelection::Uncommitted);
+    }
+    bundler
 }
 
-/// Collect matching files
-fn collect_files(base_dir: &Path, include: &GlobSet, exclude: &GlobSet) -> Vec<(PathBuf, PathBuf)> {
-    let mut results = Vec::new();
-
-    let walker = WalkDir::new(base_dir)
-        .follow_links(false)
-        .into_iter()
-        .filter_entry(|e| {
-            if e.file_type().is_dir() {
-                !should_prune_dir(e, base_dir, exclude)
-            } else {
-                true
+/// Apply `--encoding`, `--lossy` and `--embed-binary`, or their configured defaults
+fn read_options(bundler: &mut Bundler, args: &cli::ReadArgs, settings: &config::Options) {
+    let embed_binary = match (&args.embed_binary, &settings.embed_binary) {
+        (Some(text), _) | (None, Some(config::Size::Text(text))) => match parse_size(text) {
+            Some(n) => Some(n),
+            None => {
+                eprintln!("Error: invalid --embed-binary size '{}'", text);
+                std::process::exit(1);
             }
-        });
+        },
+        (None, Some(config::Size::Bytes(n))) => Some(*n),
+        (None, None) => None,
+    };
+    if let Some(max) = embed_binary {
+        bundler.embed_binary(max);
+    }
 
-    for entry in walker {
-        let entry = match entry {
-            Ok(e) => e,
-            Err(_) => continue,
+    if let Some(label) = args.encoding.as_ref().or(settings.encoding.as_ref()) {
+        match dircat::Encoding::for_label(label.as_bytes()) {
+            Some(e) => bundler.encoding(e),
+            None => {
+                eprintln!("Error: unknown encoding '{}'", label);
+                std::process::exit(1);
+            }
         };
+    }
 
-        if !entry.file_type().is_file() {
-            continue;
-        }
+    bundler.lossy(args.lossy || settings.lossy.unwrap_or(false));
+}
 
-        let full_path = entry.path();
+/// Look up the tokenizer from `--tokenizer` or the config, defaulting to cl100k
+fn tokenizer(name: Option<&str>, settings: &config::Options) -> Tokenizer {
+    let name = name.or(settings.tokenizer.as_deref()).unwrap_or("cl100k");
+    match Tokenizer::from_name(name) {
+        Some(t) => t,
+        None => {
+            eprintln!(
+                "Error: unknown tokenizer '{}' (expected cl100k, o200k or estimate)",
+                name
+            );
+            std::process::exit(1);
+        }
+    }
+}
 
-        let rel_path = match full_path.strip_prefix(base_dir) {
-            Ok(p) => p.to_path_buf(),
-            Err(_) => continue,
-        };
+/// Collect the bundle, or exit with the reason it couldn't be
+fn collect(bundler: &Bundler) -> Bundle {
+    match bundler.collect() {
+        Ok(bundle) => bundle,
+        Err(Error::NoIncludePatterns) => {
+            eprintln!(
+                "Error: no include patterns specified (pass them or set `include` in {})",
+                config::CONFIG_FILE
+            );
+            std::process::exit(1);
+        }
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
 
-        let file_name = entry.file_name();
+/// `dircat list <directory> [patterns]`: print the paths that would be bundled
+fn list_main(args: cli::SelectArgs) {
+    let settings = load_settings(&args);
+    let bundle = collect(&bundler(&args, &settings));
+    print_sensitive(&bundle, args.allow_sensitive);
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    for file in bundle.files() {
+        exit_on_write_error(writeln!(out, "{}", file.rel_path.display()), "stdout");
+    }
+}
 
-        if (include.is_match(file_name) || include.is_match(&rel_path))
-            && !exclude.is_match(file_name)
-            && !exclude.is_match(&rel_path)
-        {
-            results.push((full_path.to_path_buf(), rel_path));
+/// `dircat stats <directory> [patterns]`: per-file size, line and token counts
+fn stats_main(args: cli::StatsArgs) {
+    let settings = load_settings(&args.select);
+    let mut bundler = bundler(&args.select, &settings);
+    read_options(&mut bundler, &args.read, &settings);
+    let tokenizer = tokenizer(args.tokenizer.as_deref(), &settings);
+    let bundle = collect(&bundler);
+    print_sensitive(&bundle, args.select.allow_sensitive);
+
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    exit_on_write_error(
+        writeln!(out, "{:>10} {:>8} {:>10}  path", "tokens", "lines", "size"),
+        "stdout",
+    );
+
+    let (mut total_tokens, mut total_lines, mut total_size) = (0, 0, 0);
+    for record in bundle.records() {
+        // Unreadable files were already reported
+        if record.error.is_some() {
+            continue;
         }
+        let (tokens, lines) = match (&record.binary, &record.content) {
+            (None, Some(text)) => (tokenizer.count(text), record.lines.unwrap_or(0)),
+            _ => (0, 0),
+        };
+        total_tokens += tokens;
+        total_lines += lines;
+        total_size += record.size;
+        let lines = match record.binary {
+            None => lines.to_string(),
+            Some(_) => "binary".to_string(),
+        };
+        exit_on_write_error(
+            writeln!(
+                out,
+                "{:>10} {:>8} {:>10}  {}",
+                tokens,
+                lines,
+                dircat::format_size(record.size),
+                record.path
+            ),
+            "stdout",
+        );
     }
 
-    results.sort_by(|a, b| a.1.cmp(&b.1));
-    results
+    exit_on_write_error(
+        writeln!(
+            out,
+            "{:>10} {:>8} {:>10}  total: {} file(s) ({})",
+            total_tokens,
+            total_lines,
+            dircat::format_size(total_size),
+            bundle.files().len(),
+            tokenizer.name()
+        ),
+        "stdout",
+    );
 }
 
-/// Output Markdown to a writer
-fn output_markdown<W: Write>(files: &[(PathBuf, PathBuf)], writer: &mut W) {
-    for (i, (full_path, rel_path)) in files.iter().enumerate() {
-        writeln!(writer, "### {}", rel_path.display()).ok();
-        writeln!(writer).ok();
-
-        let lang = get_language_hint(rel_path);
-        writeln!(writer, "```{}", lang).ok();
-
-        match fs::read_to_string(full_path) {
-            Ok(content) => {
-                write!(writer, "{}", content).ok();
-                if !content.ends_with('\n') {
-                    writeln!(writer).ok();
-                }
-            }
-            Err(err) => {
-                eprintln!("Error reading {}: {}", rel_path.display(), err);
-                writeln!(writer, "[Error reading file: {}]", err).ok();
-            }
+/// Stop after a failed write, quietly if the reader went away (e.g. piped into `head`)
+fn exit_on_write_error(result: io::Result<()>, target: &str) {
+    match result {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => std::process::exit(0),
+        Err(e) => {
+            eprintln!("Error writing to {}: {}", target, e);
+            std::process::exit(1);
         }
+    }
+}
 
-        writeln!(writer, "```").ok();
+/// Print a completion script for `shell` to stdout
+fn completions_main(shell: clap_complete::Shell) {
+    let mut script = Vec::new();
+    clap_complete::generate(shell, &mut cli::Cli::command(), "dircat", &mut script);
+    exit_on_write_error(io::stdout().write_all(&script), "stdout");
+}
 
-        if i + 1 < files.len() {
-            writeln!(writer).ok();
-            writeln!(writer, "---").ok();
-            writeln!(writer).ok();
-        }
+/// Print the man page to stdout
+fn man_main() {
+    let mut page = Vec::new();
+    if let Err(e) = clap_mangen::Man::new(cli::Cli::command()).render(&mut page) {
+        eprintln!("Error rendering man page: {}", e);
+        std::process::exit(1);
     }
+    exit_on_write_error(io::stdout().write_all(&page), "stdout");
 }
 
-fn main() {
-    let args: Vec<String> = env::args().collect();
+/// Look up an output format by name, or exit if there's no such format
+fn output_format(name: &str) -> Format {
+    match Format::from_name(name) {
+        Some(f) => f,
+        None => {
+            eprintln!(
+                "Error: unknown format '{}' (expected markdown, xml, json or jsonl)",
+                name
+            );
+            std::process::exit(1);
+        }
+    }
+}
 
-    if args.len() < 3 {
+/// Refuse to overwrite an existing file that dircat didn't write, unless forced
+fn check_overwrite(path: &str, force: bool) {
+    if force {
+        return;
+    }
+    let mut head = Vec::new();
+    let existing = File::open(path).and_then(|f| f.take(1024).read_to_end(&mut head));
+    if existing.is_ok_and(|n| n > 0) && !dircat::is_bundle(&head) {
         eprintln!(
-            "Usage: dircat <directory> <patterns> [--exclude <pattern>...] [--output <file>]"
+            "Error: '{}' exists and wasn't written by dircat; pass --force to overwrite it",
+            path
         );
-        eprintln!("       Default output file is 'output.md'");
         std::process::exit(1);
     }
+}
 
-    let base_dir = PathBuf::from(&args[1]);
-    if !base_dir.is_dir() {
-        eprintln!("Error: {} is not a directory", base_dir.display());
-        std::process::exit(1);
+/// Open an output file for writing, or exit if it can't be created
+fn create_output(path: &str) -> BufWriter<File> {
+    match File::create(path) {
+        Ok(f) => BufWriter::new(f),
+        Err(e) => {
+            eprintln!("Error creating output file '{}': {}", path, e);
+            std::process::exit(1);
+        }
     }
+}
 
-    let include_patterns: Vec<String> = args[2]
-        .split(',')
-        .map(|s| s.trim().to_string())
-        .filter(|s| !s.is_empty())
+/// `dircat pack <directory> [patterns] [options]`, also the default command
+fn pack_main(args: cli::PackArgs) {
+    let settings = load_settings(&args.select);
+
+    let format = match args.format.as_ref().or(settings.format.as_ref()) {
+        None => Format::Markdown,
+        Some(name) => output_format(name),
+    };
+    let tees: Vec<(Format, String)> = args
+        .tee
+        .iter()
+        .map(|tee| match tee.split_once(':') {
+            Some((name, path)) if !path.is_empty() => (output_format(name), path.to_string()),
+            _ => {
+                eprintln!("Error: invalid --tee '{}' (expected FORMAT:FILE)", tee);
+                std::process::exit(1);
+            }
+        })
         .collect();
+    let output_file = args.output.clone().or_else(|| {
+        settings
+            .output
+            .as_ref()
+            .map(|p| p.to_string_lossy().into_owned())
+    });
+    // Piped output goes to stdout unless a file was asked for
+    let output_file = match output_file {
+        Some(path) if path == "-" => None,
+        Some(path) => Some(path),
+        None if io::stdout().is_terminal() => Some(format.default_output().to_string()),
+        None => None,
+    };
 
-    if include_patterns.is_empty() {
-        eprintln!("Error: no include patterns specified");
-        std::process::exit(1);
+    for path in output_file.iter().chain(tees.iter().map(|(_, path)| path)) {
+        check_overwrite(path, args.force);
     }
 
-    // Default exclusions for common build/dependency directories
-    let mut exclude_patterns = vec![
-        "target".to_string(),
-        "node_modules".to_string(),
-        "__pycache__".to_string(),
-        ".git".to_string(),
-        "dist".to_string(),
-        "build".to_string(),
-        "vendor".to_string(),
-        "*.lock".to_string(),
-    ];
-    let mut output_file = String::from("output.md");
-    let mut i = 3;
-    while i < args.len() {
-        if args[i] == "--exclude" {
-            if i + 1 >= args.len() {
-                eprintln!("Error: --exclude requires a pattern");
-                std::process::exit(1);
-            }
-            exclude_patterns.push(args[i + 1].clone());
-            i += 2;
-        } else if args[i] == "--output" || args[i] == "-o" {
-            if i + 1 >= args.len() {
-                eprintln!("Error: --output requires a filename");
-                std::process::exit(1);
-            }
-            output_file = args[i + 1].clone();
-            i += 2;
-        } else {
-            eprintln!("Unknown argument: {}", args[i]);
-            std::process::exit(1);
-        }
+    let mut bundler = bundler(&args.select, &settings);
+    read_options(&mut bundler, &args.read, &settings);
+    bundler.format(format).output(match &output_file {
+        Some(path) => OutputTarget::File(PathBuf::from(path)),
+        None => OutputTarget::Stdout,
+    });
+    for (_, path) in &tees {
+        bundler.output(OutputTarget::File(PathBuf::from(path)));
     }
 
-    let include_glob = match build_globset(&include_patterns) {
-        Ok(g) => g,
-        Err(e) => {
-            eprintln!("Invalid include pattern: {}", e);
+    let max_tokens = args.max_tokens.or(settings.max_tokens);
+    if args.count_tokens || max_tokens.is_some() {
+        bundler
+            .tokenizer(tokenizer(args.tokenizer.as_deref(), &settings))
+            .count_tokens(args.count_tokens);
+    }
+    if let Some(max) = max_tokens {
+        bundler.max_tokens(max);
+    }
+    let priority_patterns = if args.priority.is_empty() {
+        settings.priority.clone().unwrap_or_default()
+    } else {
+        args.priority.clone()
+    };
+    for pat in &priority_patterns {
+        bundler.priority(pat.split(',').map(str::trim).filter(|s| !s.is_empty()));
+    }
+
+    if args.with_diff || args.diff_base.is_some() {
+        bundler.with_diff(args.diff_base.as_deref().unwrap_or("HEAD"));
+    }
+
+    let show_omitted = args.tree_omitted || settings.tree_omitted.unwrap_or(false);
+    let show_tree = args.tree || settings.tree.unwrap_or(false) || show_omitted;
+    let only_json = std::iter::once(format)
+        .chain(tees.iter().map(|&(f, _)| f))
+        .all(|f| matches!(f, Format::Json | Format::JsonLines));
+    if show_tree && only_json {
+        eprintln!("Note: --tree has no effect on JSON output");
+    }
+    bundler
+        .tree(show_tree && !only_json)
+        .tree_omitted(show_omitted && !only_json)
+        .toc(args.toc || settings.toc.unwrap_or(false));
+
+    if args.watch {
+        if output_file.is_none() {
+            eprintln!("Error: --watch needs an output file; pass -o <file>");
+            std::process::exit(1);
+        }
+        if args.select.rev.is_some() {
+            eprintln!("Error: --watch can't be combined with --rev");
             std::process::exit(1);
         }
+    }
+
+    let outputs = Outputs {
+        format,
+        file: output_file,
+        tees,
     };
+    let bundle = collect(&bundler);
+    print_token_report(&bundle, args.count_tokens, max_tokens);
+    print_sensitive(&bundle, args.select.allow_sensitive);
+    outputs.write(&bundle);
 
-    let exclude_glob = match build_globset(&exclude_patterns) {
-        Ok(g) => g,
-        Err(e) => {
-            eprintln!("Invalid exclude pattern: {}", e);
-            std::process::exit(1);
+    if args.watch {
+        watch_main(&bundler, &args, &outputs, bundle, max_tokens);
+    }
+}
+
+/// Where `pack` writes the bundle: the main output and any `--tee` copies
+struct Outputs {
+    format: Format,
+    /// The main output file, or `None` for stdout
+    file: Option<String>,
+    tees: Vec<(Format, String)>,
+}
+
+impl Outputs {
+    fn files(&self) -> impl Iterator<Item = &String> {
+        self.file
+            .iter()
+            .chain(self.tees.iter().map(|(_, path)| path))
+    }
+
+    /// Render `bundle` to every output, exiting if a write fails
+    fn write(&self, bundle: &Bundle) {
+        let mut outputs: Vec<Box<dyn BundleWriter>> = vec![match &self.file {
+            Some(path) => self.format.writer(create_output(path)),
+            None => self.format.writer(BufWriter::new(io::stdout().lock())),
+        }];
+        for (tee_format, path) in &self.tees {
+            outputs.push(tee_format.writer(create_output(path)));
         }
+        let mut writers: Vec<&mut dyn BundleWriter> = outputs
+            .iter_mut()
+            .map(|w| w.as_mut() as &mut dyn BundleWriter)
+            .collect();
+        let mut targets = vec![self.file.as_deref().unwrap_or("stdout")];
+        targets.extend(self.tees.iter().map(|(_, path)| path.as_str()));
+        exit_on_write_error(bundle.write_to(&mut writers), &targets.join(", "));
+
+        for path in self.files() {
+            eprintln!("Output written to '{}'", path);
+        }
+    }
+}
+
+/// Print `--count-tokens` and `--max-tokens` results to stderr
+fn print_token_report(bundle: &Bundle, count_tokens: bool, max_tokens: Option<usize>) {
+    let Some(report) = bundle.token_report() else {
+        return;
     };
+    if count_tokens {
+        for (path, count) in &report.counts {
+            eprintln!("{:>10}  {}", count, path.display());
+        }
+    }
+    if let Some(max) = max_tokens
+        && !report.dropped.is_empty()
+    {
+        eprintln!(
+            "Dropped {} file(s) to fit the {}-token budget:",
+            report.dropped.len(),
+            max
+        );
+        for (path, count) in &report.dropped {
+            eprintln!("{:>10}  {}", count, path.display());
+        }
+    }
+    eprintln!(
+        "Total: {} tokens in {} file(s) ({})",
+        report.total,
+        bundle.files().len(),
+        report.tokenizer
+    );
+}
 
-    let files = collect_files(&base_dir, &include_glob, &exclude_glob);
+/// List the files that looked like secrets, skipped or (with `--allow-sensitive`) not
+fn print_sensitive(bundle: &Bundle, allowed: bool) {
+    let sensitive = bundle.sensitive();
+    if sensitive.is_empty() {
+        return;
+    }
+    if allowed {
+        eprintln!(
+            "Warning: including {} file(s) that may contain secrets (--allow-sensitive):",
+            sensitive.len()
+        );
+    } else {
+        eprintln!(
+            "Skipped {} file(s) that may contain secrets (pass --allow-sensitive to include them):",
+            sensitive.len()
+        );
+    }
+    for (path, reason) in sensitive {
+        eprintln!("    {}  ({})", path.display(), reason);
+    }
+}
 
-    let file = match File::create(&output_file) {
-        Ok(f) => f,
+/// `pack --watch`: rebuild the bundle whenever a file that could be in it changes
+fn watch_main(
+    bundler: &Bundler,
+    args: &cli::PackArgs,
+    outputs: &Outputs,
+    first: Bundle,
+    max_tokens: Option<usize>,
+) {
+    let base_dir = &args.select.directory;
+    let files: Vec<PathBuf> = outputs.files().map(PathBuf::from).collect();
+    let select = &args.select;
+    let watch_git = args.with_diff
+        || args.diff_base.is_some()
+        || select.staged
+        || select.uncommitted
+        || !select.commits.is_empty()
+        || !select.changed_since.is_empty();
+    let watcher = match watch::ChangeWatcher::new(base_dir, &files, watch_git) {
+        Ok(w) => w,
         Err(e) => {
-            eprintln!("Error creating output file '{}': {}", output_file, e);
+            eprintln!("Error: {}", e);
             std::process::exit(1);
         }
     };
-    let mut writer = BufWriter::new(file);
-    output_markdown(&files, &mut writer);
+    eprintln!(
+        "Watching {} for changes (Ctrl-C to stop)",
+        base_dir.display()
+    );
+
+    let mut last = Some(first);
+    loop {
+        let changed = match watcher.wait(last.as_ref()) {
+            Ok(c) => c,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        };
+        match changed.as_slice() {
+            [one] => eprintln!("Changed: {}", one.display()),
+            many => eprintln!("Changed: {} files", many.len()),
+        }
 
-    eprintln!("Output written to '{}'", output_file);
+        // Keep watching through errors, such as a half-written glob in a profile
+        last = match bundler.collect() {
+            Ok(bundle) => {
+                print_token_report(&bundle, args.count_tokens, max_tokens);
+                print_sensitive(&bundle, args.select.allow_sensitive);
+                outputs.write(&bundle);
+                Some(bundle)
+            }
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                None
+            }
+        };
+    }
+}
+
+/// What `serve` and `mcp` requests start from: everything but the include
+/// patterns, which each request may choose
+fn service_bundler(
+    select: &cli::SelectArgs,
+    read: &cli::ReadArgs,
+    settings: &config::Options,
+) -> Bundler {
+    if !select.directory.is_dir() {
+        eprintln!("Error: {} is not a directory", select.directory.display());
+        std::process::exit(1);
+    }
+    if select.allow_sensitive {
+        eprintln!(
+            "Warning: --allow-sensitive is on; files that may contain secrets will be served"
+        );
+    }
+
+    let mut bundler = selection(select, settings);
+    read_options(&mut bundler, read, settings);
+    for pat in settings.priority.iter().flatten() {
+        bundler.priority(pat.split(',').map(str::trim).filter(|s| !s.is_empty()));
+    }
+    bundler
+}
+
+/// `dircat serve <directory> [patterns] [--port <port>]`: answer bundle requests over HTTP
+fn serve_main(args: cli::ServeArgs) {
+    let settings = load_settings(&args.select);
+    let bundler = service_bundler(&args.select, &args.read, &settings);
+
+    let service = serve::Service {
+        bundler,
+        include: include_patterns(&args.select, &settings),
+        format: match args.format.as_ref().or(settings.format.as_ref()) {
+            None => Format::Markdown,
+            Some(name) => output_format(name),
+        },
+        tokenizer: tokenizer(args.tokenizer.as_deref(), &settings),
+    };
+    if let Err(e) = serve::run(&service, args.port) {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+/// `dircat mcp <directory> [patterns]`: answer Model Context Protocol requests on stdio
+fn mcp_main(args: cli::McpArgs) {
+    let settings = load_settings(&args.select);
+    let bundler = service_bundler(&args.select, &args.read, &settings);
+
+    let server = mcp::Server {
+        bundler,
+        include: include_patterns(&args.select, &settings),
+        tokenizer: tokenizer(args.tokenizer.as_deref(), &settings),
+    };
+    eprintln!(
+        "Serving {} over MCP on stdin and stdout",
+        args.select.directory.display()
+    );
+    if let Err(e) = server.run() {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+fn main() {
+    match cli::parse().command {
+        cli::Command::Pack(args) => pack_main(args),
+        cli::Command::List(args) => list_main(args),
+        cli::Command::Stats(args) => stats_main(args),
+        cli::Command::Serve(args) => serve_main(args),
+        cli::Command::Mcp(args) => mcp_main(args),
+        cli::Command::Unpack(args) => unpack_main(args),
+        cli::Command::Apply(args) => apply_main(args),
+        cli::Command::Completions { shell } => completions_main(shell),
+        cli::Command::Man => man_main(),
+    }
 }
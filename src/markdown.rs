@@ -0,0 +1,362 @@
+use std::collections::HashSet;
+use std::io::{self, Write};
+use std::sync::Arc;
+
+use crate::content::{Content, FileContent};
+use crate::git::FileDiff;
+use crate::writer::BundleWriter;
+use crate::{BUNDLE_MARKER, Bundle, SourceFile, get_language_hint};
+
+/// Pick a backtick fence longer than any backtick run in `content`
+///
+/// A fence can only be closed by a run of at least as many backticks, so files
+/// that contain ``` themselves (Markdown, doc comments) get a longer fence.
+fn markdown_fence(content: &str) -> String {
+    let mut longest = 0;
+    let mut run = 0;
+    for c in content.chars() {
+        if c == '`' {
+            run += 1;
+            longest = longest.max(run);
+        } else {
+            run = 0;
+        }
+    }
+    "`".repeat((longest + 1).max(3))
+}
+
+/// Write `content` as a fenced code block
+fn write_code_block<W: Write>(writer: &mut W, lang: &str, content: &str) -> io::Result<()> {
+    let fence = markdown_fence(content);
+    writeln!(writer, "{}{}", fence, lang)?;
+    write!(writer, "{}", content)?;
+    if !content.is_empty() && !content.ends_with('\n') {
+        writeln!(writer)?;
+    }
+    writeln!(writer, "{}", fence)
+}
+
+/// Break base64 into 76-character lines, as MIME does
+fn wrap_base64(encoded: &str) -> String {
+    let mut out = String::with_capacity(encoded.len() + encoded.len() / 76 + 1);
+    for chunk in encoded.as_bytes().chunks(76) {
+        out.push_str(std::str::from_utf8(chunk).unwrap_or_default());
+        out.push('\n');
+    }
+    out
+}
+
+/// Anchor ID for each file's section, derived from its path
+///
+/// Paths are lowercased and every run of other characters becomes a single
+/// `-`, so `src/main.rs` gets `file-src-main-rs`. Paths that collapse to the
+/// same slug get `-2`, `-3`... in sorted order, which keeps IDs stable between runs.
+pub(crate) fn anchor_ids(files: &[SourceFile]) -> Vec<String> {
+    let mut seen = HashSet::new();
+    files
+        .iter()
+        .map(|file| {
+            let mut slug = String::from("file-");
+            for c in file.rel_path.to_string_lossy().chars() {
+                if c.is_alphanumeric() {
+                    slug.extend(c.to_lowercase());
+                } else if !slug.ends_with('-') {
+                    slug.push('-');
+                }
+            }
+            let slug = slug.trim_end_matches('-').to_string();
+
+            let mut id = slug.clone();
+            let mut n = 1;
+            while !seen.insert(id.clone()) {
+                n += 1;
+                id = format!("{}-{}", slug, n);
+            }
+            id
+        })
+        .collect()
+}
+
+/// Writes the Markdown bundle: a heading and fenced code block per file
+pub struct MarkdownWriter<W> {
+    writer: W,
+    /// Anchor ID of each file's section, for the table of contents
+    anchors: Arc<[String]>,
+}
+
+impl<W: Write> MarkdownWriter<W> {
+    pub fn new(writer: W) -> MarkdownWriter<W> {
+        MarkdownWriter {
+            writer,
+            anchors: Arc::new([]),
+        }
+    }
+
+    /// A writer that can render file sections without `begin`, given the
+    /// anchors `begin` would have computed
+    pub(crate) fn with_anchors(writer: W, anchors: Arc<[String]>) -> MarkdownWriter<W> {
+        MarkdownWriter { writer, anchors }
+    }
+
+    /// Write the separator, heading and navigation line that start a file's section
+    fn section(&mut self, bundle: &Bundle, index: usize, file: &SourceFile) -> io::Result<()> {
+        let writer = &mut self.writer;
+        if index > 0 {
+            writeln!(writer)?;
+            writeln!(writer, "---")?;
+            writeln!(writer)?;
+        }
+        if bundle.toc {
+            writeln!(writer, "<a id=\"{}\"></a>", self.anchors[index])?;
+        }
+        writeln!(writer, "### {}", file.rel_path.display())?;
+        writeln!(writer)?;
+        if bundle.toc {
+            writeln!(
+                writer,
+                "File {} of {} · [Back to contents](#contents)",
+                index + 1,
+                bundle.files.len()
+            )?;
+            writeln!(writer)?;
+        }
+        Ok(())
+    }
+}
+
+/// Write one file's entry under the pending changes heading
+pub(crate) fn write_diff<W: Write>(writer: &mut W, diff: &FileDiff) -> io::Result<()> {
+    writeln!(writer)?;
+    writeln!(writer, "#### {}", diff.rel_path.display())?;
+    writeln!(writer)?;
+    write_code_block(writer, "diff", &diff.patch)
+}
+
+/// Whether the bundle has pending changes to list after the files
+fn has_changes(bundle: &Bundle) -> bool {
+    bundle.changes.as_ref().is_some_and(|c| !c.diffs.is_empty())
+}
+
+impl<W: Write> BundleWriter for MarkdownWriter<W> {
+    fn begin(&mut self, bundle: &Bundle) -> io::Result<()> {
+        let files = &bundle.files;
+        self.anchors = anchor_ids(files).into();
+        let writer = &mut self.writer;
+
+        writeln!(writer, "{}", BUNDLE_MARKER)?;
+        writeln!(writer)?;
+
+        if bundle.toc {
+            writeln!(writer, "<a id=\"contents\"></a>")?;
+            writeln!(writer, "## Contents")?;
+            writeln!(writer)?;
+            for (i, (file, anchor)) in files.iter().zip(self.anchors.iter()).enumerate() {
+                let title = file
+                    .rel_path
+                    .display()
+                    .to_string()
+                    .replace('[', "\\[")
+                    .replace(']', "\\]");
+                writeln!(writer, "{}. [{}](#{})", i + 1, title, anchor)?;
+            }
+            if has_changes(bundle) {
+                writeln!(writer)?;
+                writeln!(writer, "[Pending changes](#pending-changes)")?;
+            }
+            writeln!(writer)?;
+            writeln!(writer, "---")?;
+            writeln!(writer)?;
+        }
+
+        if let Some(tree) = &bundle.tree {
+            writeln!(writer, "## Directory tree")?;
+            writeln!(writer)?;
+            write_code_block(writer, "text", tree)?;
+            writeln!(writer)?;
+            writeln!(writer, "---")?;
+            writeln!(writer)?;
+        }
+        Ok(())
+    }
+
+    fn file(
+        &mut self,
+        bundle: &Bundle,
+        index: usize,
+        file: &SourceFile,
+        content: &FileContent,
+    ) -> io::Result<()> {
+        self.section(bundle, index, file)?;
+        let writer = &mut self.writer;
+        match &content.content {
+            Content::Text(text) => {
+                write_code_block(writer, get_language_hint(&file.rel_path), text)
+            }
+            Content::Binary { base64, .. } => {
+                writeln!(writer, "{}", content.placeholder().unwrap_or_default())?;
+                if let Some(encoded) = base64 {
+                    writeln!(writer)?;
+                    write_code_block(writer, "base64", &wrap_base64(encoded))?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn read_error(
+        &mut self,
+        bundle: &Bundle,
+        index: usize,
+        file: &SourceFile,
+        error: &io::Error,
+    ) -> io::Result<()> {
+        self.section(bundle, index, file)?;
+        write_code_block(
+            &mut self.writer,
+            get_language_hint(&file.rel_path),
+            &format!("[Error reading file: {}]\n", error),
+        )
+    }
+
+    fn end(&mut self, bundle: &Bundle) -> io::Result<()> {
+        let writer = &mut self.writer;
+        if let Some(changes) = bundle.changes.as_ref().filter(|_| has_changes(bundle)) {
+            if !bundle.files.is_empty() {
+                writeln!(writer)?;
+                writeln!(writer, "---")?;
+                writeln!(writer)?;
+            }
+            if bundle.toc {
+                writeln!(writer, "<a id=\"pending-changes\"></a>")?;
+            }
+            writeln!(writer, "## Pending changes against `{}`", changes.base)?;
+
+            for diff in &changes.diffs {
+                write_diff(writer, diff)?;
+            }
+        }
+        writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Render a code block and parse it back the way a CommonMark renderer would,
+    /// returning the opening fence and the lines inside the block
+    fn round_trip(content: &str) -> (String, Vec<String>) {
+        let mut out = Vec::new();
+        write_code_block(&mut out, "markdown", content).unwrap();
+        let rendered = String::from_utf8(out).unwrap();
+
+        let mut lines = rendered.lines();
+        let opening = lines.next().unwrap();
+        let fence: String = opening.chars().take_while(|&c| c == '`').collect();
+
+        let mut body = Vec::new();
+        let mut closed = false;
+        for line in lines.by_ref() {
+            let trimmed = line.trim_start_matches(' ');
+            let indent = line.len() - trimmed.len();
+            let run = trimmed.chars().take_while(|&c| c == '`').count();
+            if indent <= 3 && run >= fence.len() && trimmed[run..].trim().is_empty() {
+                closed = true;
+                break;
+            }
+            body.push(line.to_string());
+        }
+
+        assert!(closed, "code block was never closed:\n{}", rendered);
+        assert_eq!(
+            lines.next(),
+            None,
+            "text after the closing fence:\n{}",
+            rendered
+        );
+        (fence, body)
+    }
+
+    fn expected_lines(content: &str) -> Vec<String> {
+        content.lines().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn plain_content_uses_triple_backticks() {
+        let content = "fn main() {}\n";
+        let (fence, body) = round_trip(content);
+        assert_eq!(fence, "```");
+        assert_eq!(body, expected_lines(content));
+    }
+
+    #[test]
+    fn nested_triple_fence_gets_longer_fence() {
+        let content = "# Example\n\n```rust\nfn main() {}\n```\n\nMore text\n";
+        let (fence, body) = round_trip(content);
+        assert_eq!(fence, "````");
+        assert_eq!(body, expected_lines(content));
+    }
+
+    #[test]
+    fn longer_nested_fences_are_outgrown() {
+        let content = "`````\n````md\n```\n````\n`````\n";
+        let (fence, body) = round_trip(content);
+        assert_eq!(fence, "``````");
+        assert_eq!(body, expected_lines(content));
+    }
+
+    #[test]
+    fn indented_fence_in_doc_comment() {
+        let content = "/// ```\n/// let x = 1;\n/// ```\nfn f() {}\n   ```\n";
+        let (fence, body) = round_trip(content);
+        assert_eq!(fence, "````");
+        assert_eq!(body, expected_lines(content));
+    }
+
+    #[test]
+    fn inline_backticks_count_toward_fence() {
+        let content = "Use ``` or `` ` `` inline\n";
+        let (fence, body) = round_trip(content);
+        assert_eq!(fence, "````");
+        assert_eq!(body, expected_lines(content));
+    }
+
+    #[test]
+    fn missing_trailing_newline() {
+        let content = "last line without newline";
+        let (fence, body) = round_trip(content);
+        assert_eq!(fence, "```");
+        assert_eq!(body, expected_lines(content));
+    }
+
+    #[test]
+    fn trailing_fence_without_newline() {
+        let content = "text\n```";
+        let (fence, body) = round_trip(content);
+        assert_eq!(fence, "````");
+        assert_eq!(body, expected_lines(content));
+    }
+
+    #[test]
+    fn file_of_only_backticks() {
+        let content = "``````````";
+        let (fence, body) = round_trip(content);
+        assert_eq!(fence.len(), 11);
+        assert_eq!(body, expected_lines(content));
+    }
+
+    #[test]
+    fn tilde_fences_do_not_change_backtick_fence() {
+        let content = "~~~\ncode\n~~~\n";
+        let (fence, body) = round_trip(content);
+        assert_eq!(fence, "```");
+        assert_eq!(body, expected_lines(content));
+    }
+
+    #[test]
+    fn empty_file() {
+        let (fence, body) = round_trip("");
+        assert_eq!(fence, "```");
+        assert!(body.is_empty());
+    }
+}
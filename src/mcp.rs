@@ -0,0 +1,468 @@
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+
+use dircat::{Bundle, Bundler, Content, Tokenizer};
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+/// Protocol revisions we can speak, newest first
+const PROTOCOL_VERSIONS: &[&str] = &["2025-06-18", "2025-03-26", "2024-11-05"];
+
+/// Matching lines `search` returns unless asked for a different limit
+const MAX_RESULTS: usize = 100;
+
+// JSON-RPC error codes
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+/// A Model Context Protocol server answering tool calls about one directory
+pub struct Server {
+    /// Excludes, ignore files, git selection and read options, but no include patterns
+    pub bundler: Bundler,
+    /// Include patterns used when a tool call doesn't give any (all files if empty)
+    pub include: Vec<String>,
+    /// Tokenizer for `read_bundle`'s `max_tokens`
+    pub tokenizer: Tokenizer,
+}
+
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> RpcError {
+        RpcError {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+/// Which files a tool call covers, on top of the server's selection
+#[derive(Deserialize, Default)]
+struct Selection {
+    /// Replaces the server's include patterns when not empty
+    #[serde(default)]
+    patterns: Vec<String>,
+    /// Added to the server's excludes, never replacing them
+    #[serde(default)]
+    exclude: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct ReadBundle {
+    #[serde(flatten)]
+    select: Selection,
+    max_tokens: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct ReadFile {
+    path: String,
+    lines: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Search {
+    query: String,
+    #[serde(flatten)]
+    select: Selection,
+    #[serde(default)]
+    ignore_case: bool,
+    max_results: Option<usize>,
+}
+
+/// JSON schemas for the selection arguments every tool but `read_file` takes
+fn selection_schema() -> Value {
+    json!({
+        "patterns": {
+            "type": "array",
+            "items": { "type": "string" },
+            "description": "Glob patterns for files to include, e.g. [\"*.rs\", \"src/**/*.ts\"]; defaults to the server's patterns"
+        },
+        "exclude": {
+            "type": "array",
+            "items": { "type": "string" },
+            "description": "Glob patterns to exclude, in addition to the server's exclusions"
+        }
+    })
+}
+
+/// Descriptions and input schemas for `tools/list`
+fn tools() -> Value {
+    let selection = selection_schema();
+    let with_selection = |extra: Value| {
+        let mut properties = selection.clone();
+        if let (Some(all), Value::Object(extra)) = (properties.as_object_mut(), extra) {
+            all.extend(extra);
+        }
+        properties
+    };
+    json!([
+        {
+            "name": "list_files",
+            "description": "List the paths of the project files matching the patterns, one per line.",
+            "inputSchema": {
+                "type": "object",
+                "properties": selection,
+            }
+        },
+        {
+            "name": "read_bundle",
+            "description": "Read the matching files as one Markdown document, each file under a heading with its path.",
+            "inputSchema": {
+                "type": "object",
+                "properties": with_selection(json!({
+                    "max_tokens": {
+                        "type": "integer",
+                        "minimum": 1,
+                        "description": "Drop files until the bundle fits in this many tokens"
+                    }
+                })),
+            }
+        },
+        {
+            "name": "read_file",
+            "description": "Read one project file, or a range of its lines.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "path": {
+                        "type": "string",
+                        "description": "Path relative to the project root, as list_files prints it"
+                    },
+                    "lines": {
+                        "type": "string",
+                        "description": "1-based, inclusive line range such as \"10-40\", \"10-\" or \"25\""
+                    }
+                },
+                "required": ["path"]
+            }
+        },
+        {
+            "name": "search",
+            "description": "Find lines containing a string in the matching files, printed as path:line: text.",
+            "inputSchema": {
+                "type": "object",
+                "properties": with_selection(json!({
+                    "query": {
+                        "type": "string",
+                        "description": "Text to look for (not a regular expression)"
+                    },
+                    "ignore_case": {
+                        "type": "boolean",
+                        "description": "Match regardless of case"
+                    },
+                    "max_results": {
+                        "type": "integer",
+                        "minimum": 1,
+                        "description": "Stop after this many matching lines (default 100)"
+                    }
+                })),
+                "required": ["query"]
+            }
+        }
+    ])
+}
+
+/// Parse a 1-based, inclusive line range: `10-40`, `10-`, `-40` or `25`
+fn parse_lines(spec: &str) -> Option<(usize, Option<usize>)> {
+    let number = |s: &str| s.trim().parse::<usize>().ok().filter(|&n| n > 0);
+    let (start, end) = match spec.split_once('-') {
+        Some((start, end)) => (
+            if start.trim().is_empty() {
+                1
+            } else {
+                number(start)?
+            },
+            if end.trim().is_empty() {
+                None
+            } else {
+                Some(number(end)?)
+            },
+        ),
+        None => {
+            let line = number(spec)?;
+            (line, Some(line))
+        }
+    };
+    match end {
+        Some(end) if end < start => None,
+        _ => Some((start, end)),
+    }
+}
+
+/// The result of a tool call, with failures reported to the model rather than
+/// as protocol errors
+fn tool_result(result: Result<String, String>) -> Value {
+    let (text, is_error) = match result {
+        Ok(text) => (text, false),
+        Err(text) => (text, true),
+    };
+    json!({
+        "content": [{ "type": "text", "text": text }],
+        "isError": is_error,
+    })
+}
+
+fn arguments<T: serde::de::DeserializeOwned>(arguments: Value) -> Result<T, RpcError> {
+    serde_json::from_value(arguments)
+        .map_err(|e| RpcError::new(INVALID_PARAMS, format!("invalid arguments: {}", e)))
+}
+
+impl Server {
+    /// Collect the files a tool call covers
+    fn collect(&self, select: &Selection, max_tokens: Option<usize>) -> Result<Bundle, String> {
+        let include: Vec<String> = if !select.patterns.is_empty() {
+            select
+                .patterns
+                .iter()
+                .flat_map(|p| p.split(','))
+                .map(|p| p.trim().to_string())
+                .filter(|p| !p.is_empty())
+                .collect()
+        } else if !self.include.is_empty() {
+            self.include.clone()
+        } else {
+            vec!["*".to_string()]
+        };
+
+        let mut bundler = self.bundler.clone();
+        bundler
+            .include(include)
+            .exclude(select.exclude.iter().cloned());
+        if let Some(max) = max_tokens {
+            bundler.tokenizer(self.tokenizer).max_tokens(max);
+        }
+        bundler.collect().map_err(|e| e.to_string())
+    }
+
+    fn list_files(&self, select: Selection) -> Result<String, String> {
+        let bundle = self.collect(&select, None)?;
+        let mut lines: Vec<String> = bundle
+            .files()
+            .iter()
+            .map(|f| f.rel_path.display().to_string())
+            .collect();
+        if lines.is_empty() {
+            lines.push("No files matched".to_string());
+        }
+
+        // Say what was held back, so the model doesn't go looking for it
+        let withheld: Vec<String> = bundle
+            .sensitive()
+            .iter()
+            .filter(|(path, _)| bundle.file(path).is_none())
+            .map(|(path, reason)| format!("{} ({})", path.display(), reason))
+            .collect();
+        if !withheld.is_empty() {
+            lines.push(format!(
+                "\nWithheld because they may contain secrets: {}",
+                withheld.join(", ")
+            ));
+        }
+        Ok(lines.join("\n"))
+    }
+
+    fn read_bundle(&self, args: ReadBundle) -> Result<String, String> {
+        let bundle = self.collect(&args.select, args.max_tokens)?;
+        let mut text = Vec::new();
+        bundle.write(&mut text).map_err(|e| e.to_string())?;
+        let mut text = String::from_utf8_lossy(&text).into_owned();
+
+        // The model can't see stderr, so say what the budget left out
+        if let Some(report) = bundle.token_report()
+            && !report.dropped.is_empty()
+        {
+            text.push_str(&format!(
+                "\n\nDropped {} file(s) to fit the {}-token budget:\n",
+                report.dropped.len(),
+                args.max_tokens.unwrap_or(0)
+            ));
+            for (path, count) in &report.dropped {
+                text.push_str(&format!("- {} ({} tokens)\n", path.display(), count));
+            }
+        }
+        Ok(text)
+    }
+
+    /// Read a file that the server's selection would bundle
+    ///
+    /// The server's own include patterns apply, so a file that `list_files`
+    /// wouldn't show without patterns can't be read either.
+    fn read_file(&self, args: ReadFile) -> Result<String, String> {
+        let range = match &args.lines {
+            None => None,
+            Some(spec) => Some(parse_lines(spec).ok_or_else(|| {
+                format!(
+                    "invalid line range '{}' (expected e.g. 10-40, 10- or 25)",
+                    spec
+                )
+            })?),
+        };
+
+        let bundle = self.collect(&Selection::default(), None)?;
+        let rel_path = args.path.trim_start_matches("./");
+        let Some(file) = bundle.file(Path::new(rel_path)) else {
+            return Err(format!(
+                "'{}' isn't one of the project files (it may be excluded, ignored or withheld as sensitive)",
+                args.path
+            ));
+        };
+
+        let content = bundle.read(file).map_err(|e| e.to_string())?;
+        let text = match &content.content {
+            Content::Text(text) => text,
+            Content::Binary { .. } => return Ok(content.placeholder().unwrap_or_default()),
+        };
+        let Some((start, end)) = range else {
+            return Ok(text.clone());
+        };
+        let lines: Vec<&str> = text.split_inclusive('\n').collect();
+        if start > lines.len() {
+            return Err(format!("'{}' has only {} line(s)", args.path, lines.len()));
+        }
+        let end = end.unwrap_or(lines.len()).min(lines.len());
+        Ok(lines[start - 1..end].concat())
+    }
+
+    fn search(&self, args: Search) -> Result<String, String> {
+        if args.query.is_empty() {
+            return Err("the query is empty".to_string());
+        }
+        let max_results = args.max_results.unwrap_or(MAX_RESULTS);
+        let query = if args.ignore_case {
+            args.query.to_lowercase()
+        } else {
+            args.query.clone()
+        };
+
+        let bundle = self.collect(&args.select, None)?;
+        let mut matches = Vec::new();
+        'files: for record in bundle.records() {
+            let (None, Some(text)) = (&record.binary, &record.content) else {
+                continue;
+            };
+            for (n, line) in text.lines().enumerate() {
+                let found = if args.ignore_case {
+                    line.to_lowercase().contains(&query)
+                } else {
+                    line.contains(&query)
+                };
+                if !found {
+                    continue;
+                }
+                if matches.len() == max_results {
+                    matches.push(format!("(stopped after {} matches)", max_results));
+                    break 'files;
+                }
+                matches.push(format!("{}:{}: {}", record.path, n + 1, line.trim_end()));
+            }
+        }
+
+        if matches.is_empty() {
+            return Ok(format!("No matches for '{}'", args.query));
+        }
+        Ok(matches.join("\n"))
+    }
+
+    fn call_tool(&self, params: Value) -> Result<Value, RpcError> {
+        let name = params.get("name").and_then(Value::as_str).unwrap_or("");
+        let args = match params.get("arguments") {
+            None | Some(Value::Null) => json!({}),
+            Some(args) => args.clone(),
+        };
+        let result = match name {
+            "list_files" => self.list_files(arguments(args)?),
+            "read_bundle" => self.read_bundle(arguments(args)?),
+            "read_file" => self.read_file(arguments(args)?),
+            "search" => self.search(arguments(args)?),
+            _ => {
+                return Err(RpcError::new(
+                    INVALID_PARAMS,
+                    format!("unknown tool '{}'", name),
+                ));
+            }
+        };
+        Ok(tool_result(result))
+    }
+
+    /// Answer one message, or return `None` for notifications
+    fn handle(&self, message: &Value) -> Option<Value> {
+        let id = message.get("id").cloned();
+        let Some(method) = message.get("method").and_then(Value::as_str) else {
+            // Responses to requests we never make are ignored
+            return id
+                .map(|id| error_response(id, RpcError::new(INVALID_REQUEST, "missing method")));
+        };
+        let params = message.get("params").cloned().unwrap_or(Value::Null);
+
+        let result = match method {
+            "initialize" => {
+                let requested = params.get("protocolVersion").and_then(Value::as_str);
+                let version = PROTOCOL_VERSIONS
+                    .iter()
+                    .find(|&&v| Some(v) == requested)
+                    .unwrap_or(&PROTOCOL_VERSIONS[0]);
+                Ok(json!({
+                    "protocolVersion": version,
+                    "capabilities": { "tools": {} },
+                    "serverInfo": { "name": "dircat", "version": env!("CARGO_PKG_VERSION") },
+                    "instructions": "Tools for reading this project's source files. Excluded and ignored files, and anything inside hidden directories, are never returned, and files that may contain secrets are withheld by default.",
+                }))
+            }
+            "ping" => Ok(json!({})),
+            "tools/list" => Ok(json!({ "tools": tools() })),
+            "tools/call" => self.call_tool(params),
+            _ => Err(RpcError::new(
+                METHOD_NOT_FOUND,
+                format!("unknown method '{}'", method),
+            )),
+        };
+
+        let id = id?;
+        Some(match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err(e) => error_response(id, e),
+        })
+    }
+
+    /// Read newline-delimited JSON-RPC messages from stdin until it closes
+    pub fn run(&self) -> io::Result<()> {
+        let stdout = io::stdout();
+        for line in io::stdin().lock().lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let reply = match serde_json::from_str::<Value>(&line) {
+                Ok(message) if message.is_object() => self.handle(&message),
+                Ok(_) => Some(error_response(
+                    Value::Null,
+                    RpcError::new(INVALID_REQUEST, "expected a JSON object"),
+                )),
+                Err(e) => Some(error_response(
+                    Value::Null,
+                    RpcError::new(PARSE_ERROR, e.to_string()),
+                )),
+            };
+            if let Some(reply) = reply {
+                let mut out = stdout.lock();
+                writeln!(out, "{}", reply)?;
+                out.flush()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn error_response(id: Value, error: RpcError) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": error.code, "message": error.message },
+    })
+}
@@ -0,0 +1,119 @@
+use std::io;
+
+use serde::Serialize;
+
+use crate::content::{Content, FileContent};
+use crate::git::FileDiff;
+use crate::{Bundle, SourceFile, get_language_hint};
+
+/// Metadata and contents of one collected file
+#[derive(Serialize, Clone, Debug)]
+pub struct FileRecord {
+    /// Path relative to the base directory
+    pub path: String,
+    /// Code block language hint, e.g. `rust`
+    pub language: Option<&'static str>,
+    pub size: u64,
+    /// Line count, for text files
+    pub lines: Option<usize>,
+    /// Lowercase hex SHA-256 of the raw bytes
+    pub sha256: Option<String>,
+    /// Modification time in RFC 3339 format
+    pub modified: Option<String>,
+    /// Encoding text was decoded from
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<&'static str>,
+    /// Kind of binary file, e.g. "PNG image"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub binary: Option<&'static str>,
+    /// `base64` when `content` holds an embedded binary file
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content_encoding: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>,
+    /// Why the file couldn't be read
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Pending diff against the base revision, with `with_diff`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub diff: Option<String>,
+}
+
+/// Record for a file that is gone from the working directory but has a
+/// pending diff, written after the files' records
+#[derive(Serialize, Clone, Debug)]
+pub(crate) struct DeletedRecord<'a> {
+    pub path: String,
+    pub deleted: bool,
+    pub diff: &'a str,
+}
+
+impl DeletedRecord<'_> {
+    pub(crate) fn new(diff: &FileDiff) -> DeletedRecord<'_> {
+        DeletedRecord {
+            path: diff.rel_path.display().to_string(),
+            deleted: true,
+            diff: &diff.patch,
+        }
+    }
+}
+
+/// Build the record for one of the bundle's files from its contents or read error
+pub(crate) fn file_record(
+    bundle: &Bundle,
+    file: &SourceFile,
+    content: Result<&FileContent, &io::Error>,
+) -> FileRecord {
+    let rel_path = &file.rel_path;
+    let language = Some(get_language_hint(rel_path)).filter(|l| !l.is_empty());
+    let modified = file
+        .modified()
+        .map(|t| humantime::format_rfc3339_seconds(t).to_string());
+
+    let mut record = FileRecord {
+        path: rel_path.display().to_string(),
+        language,
+        size: file.size().unwrap_or(0),
+        lines: None,
+        sha256: None,
+        modified,
+        encoding: None,
+        binary: None,
+        content_encoding: None,
+        content: None,
+        error: None,
+        diff: bundle
+            .changes
+            .as_ref()
+            .and_then(|c| c.diff_for(rel_path))
+            .map(|d| d.patch.clone()),
+    };
+
+    let c = match content {
+        Ok(c) => c,
+        Err(err) => {
+            record.error = Some(err.to_string());
+            return record;
+        }
+    };
+
+    record.size = c.size;
+    record.sha256 = Some(c.sha256.clone());
+    record.encoding = c.encoding.map(|e| e.name());
+
+    match &c.content {
+        Content::Text(text) => {
+            record.lines = Some(text.lines().count());
+            record.content = Some(text.clone());
+        }
+        Content::Binary { kind, base64 } => {
+            record.binary = Some(kind);
+            if base64.is_some() {
+                record.content_encoding = Some("base64");
+                record.content = base64.clone();
+            }
+        }
+    }
+
+    record
+}
@@ -0,0 +1,78 @@
+use std::path::Path;
+
+/// How much of a file's start is searched for private key headers
+pub(crate) const SNIFF_LEN: u64 = 8 * 1024;
+
+/// SSH private keys, by their default file names; `*.pub` halves are fine to share
+const SSH_KEYS: &[&str] = &["id_rsa", "id_dsa", "id_ecdsa", "id_ed25519"];
+
+/// `.env` variants that are committed as documentation rather than holding secrets
+const ENV_TEMPLATES: &[&str] = &[".example", ".sample", ".template", ".dist"];
+
+/// Kubeconfig keys that embed a client's certificate and private key, whatever
+/// the file is called (often just `config`)
+const KUBE_CREDENTIAL_KEYS: &[&str] = &["client-key-data:", "client-certificate-data:"];
+
+/// Why a file name suggests credentials, if it does
+fn sensitive_name(name: &str) -> Option<&'static str> {
+    let name = name.to_ascii_lowercase();
+    let extension = name.rsplit_once('.').map(|(_, ext)| ext);
+    match name.as_str() {
+        ".env" => Some("environment file"),
+        n if n.starts_with(".env.") && !ENV_TEMPLATES.iter().any(|t| n.ends_with(t)) => {
+            Some("environment file")
+        }
+        n if SSH_KEYS.iter().any(|k| n.starts_with(k)) && !n.ends_with(".pub") => {
+            Some("SSH private key")
+        }
+        ".npmrc" | ".pypirc" | ".netrc" | "_netrc" | ".pgpass" | ".git-credentials" => {
+            Some("credentials file")
+        }
+        "credentials" | ".htpasswd" => Some("credentials file"),
+        n if n.starts_with("kubeconfig") => Some("Kubernetes config"),
+        _ => match extension {
+            Some("pem") => Some("PEM file"),
+            Some("key") => Some("key file"),
+            Some("p12" | "pfx") => Some("PKCS #12 keystore"),
+            Some("jks" | "keystore") => Some("Java keystore"),
+            Some("ppk") => Some("PuTTY private key"),
+            Some("kubeconfig") => Some("Kubernetes config"),
+            _ => None,
+        },
+    }
+}
+
+/// Whether `head` holds a PEM or PGP private key block, or a PuTTY key
+fn has_private_key(head: &[u8]) -> bool {
+    let text = String::from_utf8_lossy(head);
+    if text
+        .lines()
+        .any(|line| line.starts_with("PuTTY-User-Key-File-"))
+    {
+        return true;
+    }
+    // A BEGIN line whose label names a private key: RSA, EC, OpenSSH, PGP, ...
+    text.match_indices("-----BEGIN ").any(|(i, begin)| {
+        let rest = &text[i + begin.len()..];
+        let label_len = rest
+            .find(|c: char| !(c.is_ascii_uppercase() || c.is_ascii_digit() || c == ' '))
+            .unwrap_or(rest.len());
+        rest[..label_len].contains("PRIVATE KEY") && rest[label_len..].starts_with("-----")
+    })
+}
+
+/// Whether `head` holds a kubeconfig user entry with embedded client credentials
+fn has_kube_credentials(head: &[u8]) -> bool {
+    String::from_utf8_lossy(head).lines().any(|line| {
+        let key = line.trim_start();
+        KUBE_CREDENTIAL_KEYS.iter().any(|k| key.starts_with(k))
+    })
+}
+
+/// Why a file looks like it holds secrets, judging by its name and first bytes
+pub(crate) fn check(rel_path: &Path, head: &[u8]) -> Option<&'static str> {
+    let name = rel_path.file_name().unwrap_or_default().to_string_lossy();
+    sensitive_name(&name)
+        .or_else(|| has_private_key(head).then_some("private key"))
+        .or_else(|| has_kube_credentials(head).then_some("Kubernetes config"))
+}
@@ -0,0 +1,248 @@
+use std::path::Path;
+
+use dircat::{Bundle, Bundler, Content, Error, Format, Tokenizer};
+use percent_encoding::percent_decode_str;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+/// Host header values accepted, so pages on other sites can't reach the
+/// server through DNS rebinding
+const LOCAL_HOSTS: &[&str] = &["localhost", "127.0.0.1", "[::1]"];
+
+/// What every request starts from: the command line's selection and defaults
+pub struct Service {
+    /// Excludes, ignore files, git selection and read options, but no include patterns
+    pub bundler: Bundler,
+    /// Include patterns used when a request doesn't give any
+    pub include: Vec<String>,
+    /// Format /bundle returns when a request doesn't name one
+    pub format: Format,
+    /// Tokenizer for `max_tokens`
+    pub tokenizer: Tokenizer,
+}
+
+/// A response ready to send
+struct Reply {
+    status: u16,
+    content_type: &'static str,
+    body: Vec<u8>,
+}
+
+impl Reply {
+    fn ok(content_type: &'static str, body: Vec<u8>) -> Reply {
+        Reply {
+            status: 200,
+            content_type,
+            body,
+        }
+    }
+
+    fn error(status: u16, message: impl std::fmt::Display) -> Reply {
+        Reply {
+            status,
+            content_type: "text/plain; charset=utf-8",
+            body: format!("{}\n", message).into_bytes(),
+        }
+    }
+}
+
+/// Query parameters shared by the endpoints
+#[derive(Default)]
+struct Query {
+    /// Replaces the default include patterns when not empty
+    include: Vec<String>,
+    /// Added to the command line's excludes, never replacing them
+    exclude: Vec<String>,
+    format: Option<Format>,
+    max_tokens: Option<usize>,
+    tree: bool,
+    toc: bool,
+}
+
+/// Parse a query string, rejecting unknown parameters so typos don't go unnoticed
+fn parse_query(query: &str) -> Result<Query, String> {
+    let mut parsed = Query::default();
+    for (key, value) in form_urlencoded::parse(query.as_bytes()) {
+        let flag = || match value.as_ref() {
+            "" | "1" | "true" => Ok(true),
+            "0" | "false" => Ok(false),
+            _ => Err(format!(
+                "invalid {} '{}' (expected true or false)",
+                key, value
+            )),
+        };
+        match key.as_ref() {
+            "include" => parsed.include.extend(
+                value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|s| !s.is_empty())
+                    .map(str::to_string),
+            ),
+            "exclude" => parsed.exclude.push(value.to_string()),
+            "format" => match Format::from_name(&value) {
+                Some(f) => parsed.format = Some(f),
+                None => {
+                    return Err(format!(
+                        "unknown format '{}' (expected markdown, xml, json or jsonl)",
+                        value
+                    ));
+                }
+            },
+            "max_tokens" => match value.parse() {
+                Ok(n) => parsed.max_tokens = Some(n),
+                Err(_) => return Err(format!("invalid max_tokens '{}'", value)),
+            },
+            "tree" => parsed.tree = flag()?,
+            "toc" => parsed.toc = flag()?,
+            _ => return Err(format!("unknown parameter '{}'", key)),
+        }
+    }
+    Ok(parsed)
+}
+
+fn content_type(format: Format) -> &'static str {
+    match format {
+        Format::Markdown => "text/markdown; charset=utf-8",
+        Format::Xml => "application/xml; charset=utf-8",
+        Format::Json => "application/json",
+        Format::JsonLines => "application/x-ndjson",
+    }
+}
+
+impl Service {
+    /// Collect a fresh bundle for one request
+    fn collect(&self, query: &Query) -> Result<Bundle, Reply> {
+        let format = query.format.unwrap_or(self.format);
+        let mut bundler = self.bundler.clone();
+        bundler
+            .include(if query.include.is_empty() {
+                self.include.clone()
+            } else {
+                query.include.clone()
+            })
+            .exclude(query.exclude.iter().cloned())
+            .format(format)
+            .tree(query.tree && matches!(format, Format::Markdown | Format::Xml))
+            .toc(query.toc);
+        if let Some(max) = query.max_tokens {
+            bundler.tokenizer(self.tokenizer).max_tokens(max);
+        }
+
+        bundler.collect().map_err(|e| match e {
+            Error::NoIncludePatterns => {
+                Reply::error(400, "no include patterns (pass include=<patterns>)")
+            }
+            Error::InvalidPattern { .. } => Reply::error(400, e),
+            e => Reply::error(500, e),
+        })
+    }
+
+    /// `GET /bundle`: the whole bundle, in the requested format
+    fn bundle(&self, query: &Query) -> Result<Reply, Reply> {
+        let bundle = self.collect(query)?;
+        let mut body = Vec::new();
+        bundle.write(&mut body).map_err(|e| Reply::error(500, e))?;
+        Ok(Reply::ok(content_type(bundle.format()), body))
+    }
+
+    /// `GET /files`: the selected paths and sizes as a JSON array
+    fn files(&self, query: &Query) -> Result<Reply, Reply> {
+        let bundle = self.collect(query)?;
+        let files: Vec<_> = bundle
+            .files()
+            .iter()
+            .map(|f| {
+                serde_json::json!({
+                    "path": f.rel_path.display().to_string(),
+                    "size": f.size(),
+                })
+            })
+            .collect();
+        let body = serde_json::to_vec_pretty(&files).map_err(|e| Reply::error(500, e))?;
+        Ok(Reply::ok("application/json", body))
+    }
+
+    /// `GET /file/<path>`: one selected file, as decoded text or raw bytes
+    ///
+    /// Only files the bundle would contain are served, so excluded and ignored
+    /// files stay out of reach however the path is spelled.
+    fn file(&self, path: &str, query: &Query) -> Result<Reply, Reply> {
+        let path = percent_decode_str(path)
+            .decode_utf8()
+            .map_err(|_| Reply::error(400, "path isn't valid UTF-8"))?;
+        let bundle = self.collect(query)?;
+        let Some(file) = bundle.file(Path::new(path.as_ref())) else {
+            return Err(Reply::error(404, format!("'{}' isn't in the bundle", path)));
+        };
+
+        let content = bundle.read(file).map_err(|e| Reply::error(500, e))?;
+        match content.content {
+            Content::Text(text) => Ok(Reply::ok("text/plain; charset=utf-8", text.into_bytes())),
+            Content::Binary { .. } => {
+                let bytes = file.read_bytes().map_err(|e| Reply::error(500, e))?;
+                Ok(Reply::ok("application/octet-stream", bytes))
+            }
+        }
+    }
+
+    fn handle(&self, request: &Request) -> Reply {
+        let host = request
+            .headers()
+            .iter()
+            .find(|h| h.field.equiv("Host"))
+            .map(|h| h.value.as_str());
+        if let Some(host) = host {
+            let name = match host.rsplit_once(':') {
+                Some((name, port)) if !port.contains(']') => name,
+                _ => host,
+            };
+            if !LOCAL_HOSTS.contains(&name) {
+                return Reply::error(403, format!("unexpected host '{}'", host));
+            }
+        }
+        if *request.method() != Method::Get {
+            return Reply::error(405, "only GET is supported");
+        }
+
+        let (path, query) = request.url().split_once('?').unwrap_or((request.url(), ""));
+        let query = match parse_query(query) {
+            Ok(q) => q,
+            Err(e) => return Reply::error(400, e),
+        };
+        let result = match path {
+            "/bundle" => self.bundle(&query),
+            "/files" => self.files(&query),
+            _ => match path.strip_prefix("/file/") {
+                Some(rel_path) => self.file(rel_path, &query),
+                None => Err(Reply::error(
+                    404,
+                    "not found (try /bundle, /files or /file/<path>)",
+                )),
+            },
+        };
+        result.unwrap_or_else(|reply| reply)
+    }
+}
+
+/// Listen on 127.0.0.1:`port` and answer requests one at a time, until killed
+pub fn run(service: &Service, port: u16) -> Result<(), String> {
+    let server = Server::http(("127.0.0.1", port)).map_err(|e| e.to_string())?;
+    eprintln!(
+        "Serving on http://{} (Ctrl-C to stop)",
+        server.server_addr()
+    );
+
+    for request in server.incoming_requests() {
+        let reply = service.handle(&request);
+        eprintln!("{} {} {}", request.method(), request.url(), reply.status);
+        let header = Header::from_bytes("Content-Type", reply.content_type)
+            .expect("content types are valid header values");
+        let response = Response::from_data(reply.body)
+            .with_status_code(reply.status)
+            .with_header(header);
+        if let Err(e) = request.respond(response) {
+            eprintln!("Error responding: {}", e);
+        }
+    }
+    Ok(())
+}
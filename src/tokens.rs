@@ -0,0 +1,111 @@
+use std::path::Path;
+
+use globset::GlobSet;
+use tiktoken_rs::CoreBPE;
+
+/// Counts tokens using an embedded BPE vocabulary or a cheap character estimate
+#[derive(Clone, Copy)]
+pub enum Tokenizer {
+    /// `cl100k_base`, used by GPT-4 and GPT-3.5 class models
+    Cl100k(&'static CoreBPE),
+    /// `o200k_base`, used by GPT-4o and newer models
+    O200k(&'static CoreBPE),
+    /// Roughly four characters per token, no vocabulary needed
+    Estimate,
+}
+
+impl Tokenizer {
+    /// Look up a tokenizer by its command-line name
+    pub fn from_name(name: &str) -> Option<Tokenizer> {
+        match name {
+            "cl100k" | "cl100k_base" => {
+                Some(Tokenizer::Cl100k(tiktoken_rs::cl100k_base_singleton()))
+            }
+            "o200k" | "o200k_base" => Some(Tokenizer::O200k(tiktoken_rs::o200k_base_singleton())),
+            "estimate" | "chars" => Some(Tokenizer::Estimate),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Tokenizer::Cl100k(_) => "cl100k",
+            Tokenizer::O200k(_) => "o200k",
+            Tokenizer::Estimate => "estimate",
+        }
+    }
+
+    /// Number of tokens in `text`
+    pub fn count(&self, text: &str) -> usize {
+        match self {
+            Tokenizer::Cl100k(bpe) | Tokenizer::O200k(bpe) => bpe.encode_ordinary(text).len(),
+            Tokenizer::Estimate => estimate_tokens(text),
+        }
+    }
+}
+
+impl Default for Tokenizer {
+    fn default() -> Tokenizer {
+        Tokenizer::Cl100k(tiktoken_rs::cl100k_base_singleton())
+    }
+}
+
+/// Cheap token estimate: one token per four characters, rounded up
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}
+
+/// Result of fitting files into a token budget
+pub struct BudgetSelection {
+    /// Indices of the files that fit, in their original order
+    pub kept: Vec<usize>,
+    /// Indices of the files that did not fit, in priority order
+    pub dropped: Vec<usize>,
+    /// Total tokens of the kept files
+    pub total: usize,
+}
+
+/// Greedily pick files in priority order until `max_tokens` is exhausted
+///
+/// Each item is a path and its token count: a bundled file, or the diff of a
+/// deleted one.
+/// Files matching an earlier priority pattern are considered first; ties (and
+/// files matching no pattern) keep their sorted order. A file that doesn't fit
+/// is dropped, but smaller files after it may still be taken.
+pub fn select_within_budget(
+    paths: &[&Path],
+    token_counts: &[usize],
+    priority: &[GlobSet],
+    max_tokens: usize,
+) -> BudgetSelection {
+    let rank = |rel_path: &Path| {
+        let file_name = rel_path.file_name().unwrap_or_default();
+        priority
+            .iter()
+            .position(|set| set.is_match(rel_path) || set.is_match(file_name))
+            .unwrap_or(priority.len())
+    };
+
+    let mut order: Vec<usize> = (0..paths.len()).collect();
+    order.sort_by_key(|&i| rank(paths[i]));
+
+    let mut kept = Vec::new();
+    let mut dropped = Vec::new();
+    let mut total = 0;
+
+    for i in order {
+        if total + token_counts[i] <= max_tokens {
+            total += token_counts[i];
+            kept.push(i);
+        } else {
+            dropped.push(i);
+        }
+    }
+
+    kept.sort_unstable();
+    BudgetSelection {
+        kept,
+        dropped,
+        total,
+    }
+}
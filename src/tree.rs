@@ -0,0 +1,150 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use rayon::prelude::*;
+
+use crate::content::{self, Content};
+use crate::tokens::estimate_tokens;
+use crate::{Bundle, SourceFile};
+
+/// A directory level of the rendered tree
+#[derive(Default)]
+struct Dir {
+    entries: BTreeMap<String, Node>,
+}
+
+enum Node {
+    Dir(Dir),
+    File(String),
+    Omitted,
+}
+
+impl Dir {
+    /// Find or create the subdirectory for each component of `rel_dir`
+    fn subdir(&mut self, rel_dir: &Path) -> &mut Dir {
+        let mut dir = self;
+        for part in rel_dir.iter() {
+            let name = part.to_string_lossy().into_owned();
+            let node = dir
+                .entries
+                .entry(name)
+                .or_insert_with(|| Node::Dir(Dir::default()));
+            if !matches!(node, Node::Dir(_)) {
+                *node = Node::Dir(Dir::default());
+            }
+            let Node::Dir(sub) = node else {
+                unreachable!();
+            };
+            dir = sub;
+        }
+        dir
+    }
+
+    fn insert(&mut self, rel_path: &Path, node: Node) {
+        let Some(name) = rel_path.file_name() else {
+            return;
+        };
+        let parent = rel_path.parent().unwrap_or(Path::new(""));
+        self.subdir(parent)
+            .entries
+            .entry(name.to_string_lossy().into_owned())
+            .or_insert(node);
+    }
+
+    fn render(&self, prefix: &str, out: &mut String) {
+        let count = self.entries.len();
+        for (i, (name, node)) in self.entries.iter().enumerate() {
+            let last = i + 1 == count;
+            let branch = if last { "└── " } else { "├── " };
+            match node {
+                Node::Dir(sub) => {
+                    out.push_str(&format!("{}{}{}/\n", prefix, branch, name));
+                    let indent = if last { "    " } else { "│   " };
+                    sub.render(&format!("{}{}", prefix, indent), out);
+                }
+                Node::File(note) => {
+                    out.push_str(&format!("{}{}{} ({})\n", prefix, branch, name, note));
+                }
+                Node::Omitted => {
+                    out.push_str(&format!("{}{}{}/ (omitted)\n", prefix, branch, name));
+                }
+            }
+        }
+    }
+}
+
+/// Human-readable byte count, e.g. `812 B` or `4.2 KiB`
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit + 1 < UNITS.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+/// Annotation for one file, along with its size and token estimate
+fn describe(bundle: &Bundle, file: &SourceFile) -> (String, u64, usize) {
+    match content::read_content(file, &bundle.read_options) {
+        Ok(c) => match &c.content {
+            Content::Text(text) => {
+                let tokens = estimate_tokens(text);
+                let lines = text.lines().count();
+                let note = format!(
+                    "{}, {} line{}, ~{} tokens",
+                    format_size(c.size),
+                    lines,
+                    if lines == 1 { "" } else { "s" },
+                    tokens
+                );
+                (note, c.size, tokens)
+            }
+            Content::Binary { kind, .. } => {
+                (format!("{}, {}", format_size(c.size), kind), c.size, 0)
+            }
+        },
+        Err(_) => ("unreadable".to_string(), 0, 0),
+    }
+}
+
+/// Render the bundled files as a `tree`-style listing
+///
+/// Each file is annotated with its size, line count and a chars/4 token
+/// estimate. Directories in `omitted` are listed but marked as omitted.
+pub fn render_tree(root: &str, bundle: &Bundle, omitted: &[PathBuf]) -> String {
+    let mut top = Dir::default();
+    let mut total_size = 0;
+    let mut total_tokens = 0;
+
+    let described: Vec<_> = bundle.pool.install(|| {
+        bundle
+            .files
+            .par_iter()
+            .map(|file| describe(bundle, file))
+            .collect()
+    });
+    for (file, (note, size, tokens)) in bundle.files.iter().zip(described) {
+        total_size += size;
+        total_tokens += tokens;
+        top.insert(&file.rel_path, Node::File(note));
+    }
+    for dir in omitted {
+        top.insert(dir, Node::Omitted);
+    }
+
+    let mut out = format!("{}\n", root);
+    top.render("", &mut out);
+    out.push_str(&format!(
+        "\n{} file{}, {}, ~{} tokens\n",
+        bundle.files.len(),
+        if bundle.files.len() == 1 { "" } else { "s" },
+        format_size(total_size),
+        total_tokens
+    ));
+    out
+}
@@ -0,0 +1,746 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Component, Path, PathBuf};
+
+use base64::Engine;
+use serde_json::Value;
+
+/// One file recovered from a bundle
+pub struct BundleFile {
+    pub path: String,
+    pub content: Vec<u8>,
+}
+
+/// Prefix of the placeholder written when a file couldn't be read
+const READ_ERROR_PREFIX: &str = "[Error reading file:";
+
+/// Prefix of the placeholder written in place of a binary file
+const BINARY_PREFIX: &str = "[Binary file:";
+
+/// Decode an embedded binary file, ignoring the line breaks it was wrapped at
+fn decode_base64(path: &str, text: &str) -> Option<Vec<u8>> {
+    let compact: String = text.split_whitespace().collect();
+    match base64::engine::general_purpose::STANDARD.decode(compact) {
+        Ok(bytes) => Some(bytes),
+        Err(e) => {
+            eprintln!("Skipping {}: invalid base64 contents: {}", path, e);
+            None
+        }
+    }
+}
+
+/// Report a file whose contents were left out of the bundle
+fn skip_missing(path: &str) {
+    eprintln!("Skipping {}: bundle has no contents for it", path);
+}
+
+/// Parse a bundle in any of the formats dircat writes
+///
+/// The format is detected from the first non-blank character after the
+/// generated-by marker: `<` for XML, `[` for JSON, `{` for JSON Lines,
+/// anything else for Markdown.
+pub fn parse_bundle(text: &str) -> Result<Vec<BundleFile>, String> {
+    let trimmed = text.trim_start();
+    let trimmed = trimmed
+        .strip_prefix(dircat::BUNDLE_MARKER)
+        .unwrap_or(trimmed)
+        .trim_start();
+    if trimmed.starts_with("<documents") || trimmed.starts_with("<?xml") {
+        parse_xml(text)
+    } else if trimmed.starts_with('[') {
+        parse_json(text)
+    } else if trimmed.starts_with('{') {
+        parse_jsonl(text)
+    } else {
+        Ok(parse_markdown(text))
+    }
+}
+
+/// If `line` opens a code fence, return the fence character and length
+pub fn opening_fence(line: &str) -> Option<(char, usize)> {
+    let trimmed = line.trim_start_matches(' ');
+    if line.len() - trimmed.len() > 3 {
+        return None;
+    }
+    let c = trimmed.chars().next()?;
+    if c != '`' && c != '~' {
+        return None;
+    }
+    let len = trimmed.chars().take_while(|&x| x == c).count();
+    if len < 3 || (c == '`' && trimmed[len..].contains('`')) {
+        return None;
+    }
+    Some((c, len))
+}
+
+/// Whether `line` closes a fence opened with `len` copies of `c`
+pub fn is_closing_fence(line: &str, c: char, len: usize) -> bool {
+    let trimmed = line.trim_start_matches(' ');
+    if line.len() - trimmed.len() > 3 {
+        return false;
+    }
+    let run = trimmed.chars().take_while(|&x| x == c).count();
+    run >= len && trimmed[run..].trim().is_empty()
+}
+
+/// `line` without its `\n` or `\r\n` ending
+fn strip_line_ending(line: &str) -> &str {
+    let line = line.strip_suffix('\n').unwrap_or(line);
+    line.strip_suffix('\r').unwrap_or(line)
+}
+
+/// Parse the `### path` + fenced block layout written by the Markdown output
+///
+/// Anything between a heading and its code block (anchors, back-links, notes)
+/// is skipped, so hand-edited bundles and LLM responses still parse. Binary
+/// files are restored from a following `base64` block when one was embedded.
+pub fn parse_markdown(text: &str) -> Vec<BundleFile> {
+    let mut files = Vec::new();
+    // Lines keep their endings, so CRLF files come back byte for byte
+    let mut lines = text.split_inclusive('\n');
+    let mut pending_path: Option<String> = None;
+    let mut binary = false;
+
+    while let Some(line) = lines.next() {
+        let line = strip_line_ending(line);
+        if let Some(heading) = line.strip_prefix("### ") {
+            if let Some(path) = pending_path.take().filter(|_| binary) {
+                skip_missing(&path);
+            }
+            let path = heading.trim().trim_matches('`').to_string();
+            pending_path = Some(path).filter(|p| !p.is_empty());
+            binary = false;
+            continue;
+        }
+
+        if pending_path.is_some() && line.starts_with(BINARY_PREFIX) {
+            binary = true;
+            continue;
+        }
+
+        let Some((fence_char, fence_len)) = opening_fence(line) else {
+            continue;
+        };
+        let info = line.trim_start_matches(' ')[fence_len..].trim();
+
+        let mut content = String::new();
+        for inner in lines.by_ref() {
+            if is_closing_fence(strip_line_ending(inner), fence_char, fence_len) {
+                break;
+            }
+            content.push_str(inner);
+            if !inner.ends_with('\n') {
+                content.push('\n');
+            }
+        }
+
+        if let Some(path) = pending_path.take() {
+            if binary {
+                binary = false;
+                if info != "base64" {
+                    skip_missing(&path);
+                    continue;
+                }
+                if let Some(content) = decode_base64(&path, &content) {
+                    files.push(BundleFile { path, content });
+                }
+                continue;
+            }
+            if content.starts_with(READ_ERROR_PREFIX) {
+                skip_missing(&path);
+                continue;
+            }
+            files.push(BundleFile {
+                path,
+                content: content.into_bytes(),
+            });
+        }
+    }
+
+    if let Some(path) = pending_path.filter(|_| binary) {
+        skip_missing(&path);
+    }
+
+    files
+}
+
+/// Replace the predefined XML entities and numeric character references
+fn unescape_xml(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let Some(semi) = rest.find(';') else {
+            break;
+        };
+        let entity = &rest[1..semi];
+        let decoded = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => entity
+                .strip_prefix("#x")
+                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                .or_else(|| entity.strip_prefix('#').and_then(|dec| dec.parse().ok()))
+                .and_then(char::from_u32),
+        };
+        match decoded {
+            Some(c) => {
+                out.push(c);
+                rest = &rest[semi + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Decode element text made of CDATA sections and escaped character data
+fn decode_xml_text(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("<![CDATA[") {
+        out.push_str(&unescape_xml(&rest[..start]));
+        rest = &rest[start + "<![CDATA[".len()..];
+        match rest.find("]]>") {
+            Some(end) => {
+                out.push_str(&rest[..end]);
+                rest = &rest[end + "]]>".len()..];
+            }
+            None => {
+                out.push_str(rest);
+                rest = "";
+            }
+        }
+    }
+    out.push_str(&unescape_xml(rest));
+    out
+}
+
+/// Position of the first `pattern` in `text` that isn't inside a CDATA section
+fn find_markup(text: &str, pattern: &str) -> Option<usize> {
+    let mut pos = 0;
+    while pos < text.len() {
+        let rest = &text[pos..];
+        let next_cdata = rest.find("<![CDATA[");
+        let next_match = rest.find(pattern);
+        match (next_match, next_cdata) {
+            (Some(m), Some(c)) if c < m => {
+                let body = pos + c + "<![CDATA[".len();
+                pos = match text[body..].find("]]>") {
+                    Some(end) => body + end + "]]>".len(),
+                    None => return None,
+                };
+            }
+            (Some(m), _) => return Some(pos + m),
+            (None, _) => return None,
+        }
+    }
+    None
+}
+
+/// Attributes and text of the first `<tag ...>...</tag>` element, ignoring
+/// anything that looks like markup inside CDATA
+fn element<'a>(text: &'a str, tag: &str) -> Option<(&'a str, &'a str)> {
+    let open = format!("<{}", tag);
+    let close = format!("</{}>", tag);
+    let mut pos = 0;
+    loop {
+        let at = pos + find_markup(&text[pos..], &open)?;
+        let after = at + open.len();
+        if matches!(text[after..].chars().next(), Some('>' | ' ')) {
+            let tag_end = after + text[after..].find('>')?;
+            let start = tag_end + 1;
+            let end = start + find_markup(&text[start..], &close)?;
+            return Some((&text[after..tag_end], &text[start..end]));
+        }
+        pos = after;
+    }
+}
+
+/// Value of `name="..."` in an element's attribute list
+fn attribute(attrs: &str, name: &str) -> Option<String> {
+    let key = format!(" {}=\"", name);
+    let start = attrs.find(&key)? + key.len();
+    let end = start + attrs[start..].find('"')?;
+    Some(unescape_xml(&attrs[start..end]))
+}
+
+/// Start of the next `<document>` element
+fn next_document(text: &str) -> Option<usize> {
+    let mut pos = 0;
+    while let Some(found) = find_markup(&text[pos..], "<document") {
+        let at = pos + found;
+        let after = text[at + "<document".len()..].chars().next();
+        if matches!(after, Some('>' | ' ' | '\t' | '\n' | '\r')) {
+            return Some(at);
+        }
+        pos = at + "<document".len();
+    }
+    None
+}
+
+/// Parse the `<documents>` layout written by the XML output
+pub fn parse_xml(text: &str) -> Result<Vec<BundleFile>, String> {
+    let mut files = Vec::new();
+    let mut rest = text;
+
+    while let Some(doc_start) = next_document(rest) {
+        rest = &rest[doc_start..];
+        let doc_end = find_markup(rest, "</document>")
+            .ok_or_else(|| "unterminated <document> element".to_string())?;
+        let doc = &rest[..doc_end];
+        rest = &rest[doc_end + "</document>".len()..];
+
+        let (_, source) =
+            element(doc, "source").ok_or_else(|| "<document> without <source>".to_string())?;
+        let path = decode_xml_text(source).trim().to_string();
+        let (attrs, raw) = element(doc, "document_content")
+            .ok_or_else(|| format!("<document> for {} without <document_content>", path))?;
+
+        if attribute(attrs, "encoding").as_deref() == Some("base64") {
+            if let Some(content) = decode_base64(&path, raw) {
+                files.push(BundleFile { path, content });
+            }
+            continue;
+        }
+
+        let raw = raw.strip_prefix('\n').unwrap_or(raw);
+        let raw = raw.strip_suffix('\n').unwrap_or(raw);
+        let content = decode_xml_text(raw);
+
+        if !raw.trim_start().starts_with("<![CDATA[")
+            && (content.starts_with(READ_ERROR_PREFIX) || content.starts_with(BINARY_PREFIX))
+        {
+            skip_missing(&path);
+            continue;
+        }
+        files.push(BundleFile {
+            path,
+            content: content.into_bytes(),
+        });
+    }
+
+    Ok(files)
+}
+
+fn json_record(value: &Value) -> Option<BundleFile> {
+    // Deleted files only carry their diff
+    if value.get("deleted").and_then(Value::as_bool) == Some(true) {
+        return None;
+    }
+    let path = value.get("path")?.as_str()?.to_string();
+    let Some(content) = value.get("content").and_then(Value::as_str) else {
+        skip_missing(&path);
+        return None;
+    };
+    if value.get("content_encoding").and_then(Value::as_str) == Some("base64") {
+        let content = decode_base64(&path, content)?;
+        return Some(BundleFile { path, content });
+    }
+    Some(BundleFile {
+        path,
+        content: content.as_bytes().to_vec(),
+    })
+}
+
+/// Parse the array written by the JSON output
+pub fn parse_json(text: &str) -> Result<Vec<BundleFile>, String> {
+    let values: Vec<Value> = serde_json::from_str(text).map_err(|e| e.to_string())?;
+    Ok(values.iter().filter_map(json_record).collect())
+}
+
+/// Parse the records written by the JSON Lines output
+pub fn parse_jsonl(text: &str) -> Result<Vec<BundleFile>, String> {
+    let mut files = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let value: Value =
+            serde_json::from_str(line).map_err(|e| format!("line {}: {}", i + 1, e))?;
+        files.extend(json_record(&value));
+    }
+    Ok(files)
+}
+
+/// Join a bundle path onto `target`, rejecting anything that could escape it
+///
+/// Absolute paths, drive prefixes and `..` components are refused outright.
+pub fn safe_join(target: &Path, rel: &str) -> Result<PathBuf, String> {
+    let rel_path = Path::new(rel);
+    let mut joined = target.to_path_buf();
+    let mut depth = 0;
+
+    for component in rel_path.components() {
+        match component {
+            Component::Normal(part) => {
+                joined.push(part);
+                depth += 1;
+            }
+            Component::CurDir => {}
+            Component::ParentDir => return Err(format!("{}: path contains '..'", rel)),
+            Component::RootDir | Component::Prefix(_) => {
+                return Err(format!("{}: path is absolute", rel));
+            }
+        }
+    }
+
+    if depth == 0 {
+        return Err(format!("{}: empty path", rel));
+    }
+    Ok(joined)
+}
+
+/// Make sure writing to `dest` stays inside `target` once symlinks are resolved
+pub fn check_resolved(target: &Path, dest: &Path, rel: &str) -> Result<(), String> {
+    if fs::symlink_metadata(dest).is_ok_and(|m| m.file_type().is_symlink()) {
+        return Err(format!("{}: refusing to write through a symlink", rel));
+    }
+
+    let canonical_target = target
+        .canonicalize()
+        .map_err(|e| format!("{}: {}", target.display(), e))?;
+
+    // Find the deepest ancestor that already exists and resolve it
+    let mut existing = dest.parent();
+    while let Some(dir) = existing {
+        if dir.exists() {
+            break;
+        }
+        existing = dir.parent();
+    }
+    let Some(dir) = existing else {
+        return Ok(());
+    };
+    let canonical_dir = dir
+        .canonicalize()
+        .map_err(|e| format!("{}: {}", dir.display(), e))?;
+
+    if canonical_dir.starts_with(&canonical_target) {
+        Ok(())
+    } else {
+        Err(format!("{}: resolves outside {}", rel, target.display()))
+    }
+}
+
+/// Options for `dircat unpack`
+pub struct UnpackOptions {
+    pub dry_run: bool,
+    pub diff: bool,
+}
+
+/// What happened (or would happen) to each file
+#[derive(Default)]
+pub struct UnpackSummary {
+    pub created: usize,
+    pub updated: usize,
+    pub unchanged: usize,
+    pub rejected: usize,
+}
+
+/// Write bundle files under `target`
+pub fn unpack(
+    files: &[BundleFile],
+    target: &Path,
+    options: &UnpackOptions,
+) -> io::Result<UnpackSummary> {
+    let mut summary = UnpackSummary::default();
+
+    if !options.dry_run {
+        fs::create_dir_all(target)?;
+    }
+
+    for file in files {
+        let dest = match safe_join(target, &file.path)
+            .and_then(|d| check_resolved(target, &d, &file.path).map(|_| d))
+        {
+            Ok(d) => d,
+            Err(e) => {
+                eprintln!("Rejected {}", e);
+                summary.rejected += 1;
+                continue;
+            }
+        };
+
+        let existing = fs::read(&dest).ok();
+        let action = match &existing {
+            None => "create",
+            Some(old) if *old == file.content => "unchanged",
+            Some(_) => "update",
+        };
+
+        if options.diff && action != "unchanged" {
+            let old = existing.as_deref().unwrap_or_default();
+            match (std::str::from_utf8(old), std::str::from_utf8(&file.content)) {
+                (Ok(old), Ok(new)) => {
+                    let patch = diffy::DiffOptions::new()
+                        .set_original_filename(format!("a/{}", file.path))
+                        .set_modified_filename(format!("b/{}", file.path))
+                        .create_patch(old, new);
+                    write!(io::stdout(), "{}", patch)?;
+                }
+                _ => writeln!(
+                    io::stdout(),
+                    "Binary files a/{0} and b/{0} differ",
+                    file.path
+                )?,
+            }
+        }
+
+        match action {
+            "create" => summary.created += 1,
+            "update" => summary.updated += 1,
+            _ => summary.unchanged += 1,
+        }
+
+        if options.dry_run {
+            eprintln!("{:<9} {}", action, file.path);
+            continue;
+        }
+
+        if action != "unchanged" {
+            if let Some(parent) = dest.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(&dest, &file.content)?;
+        }
+    }
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    use dircat::{Bundler, Format};
+
+    /// A fresh, empty directory under the system temp dir, unique to the test
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("dircat-unpack-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn git(dir: &Path, args: &[&str]) {
+        let status = Command::new("git")
+            .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+            .args(["-c", "commit.gpgsign=false"])
+            .args(args)
+            .current_dir(dir)
+            .status()
+            .unwrap();
+        assert!(status.success(), "git {:?} failed", args);
+    }
+
+    /// Files the round trips must bring back exactly
+    const TEXT_FILES: &[(&str, &[u8])] = &[
+        ("README.md", b"# Demo\n\n```rust\nfn main() {}\n```\n"),
+        ("crlf.txt", b"first\r\nsecond\r\n"),
+        ("src/lib.rs", b"pub fn answer() -> u32 {\n    42\n}\n"),
+    ];
+
+    /// A repository with a modified, a deleted, new text files and a binary
+    /// file, so bundles get a tree, contents, pending changes and a placeholder
+    fn sample_repo(name: &str) -> PathBuf {
+        let root = temp_dir(name);
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(
+            root.join("src/lib.rs"),
+            "pub fn answer() -> u32 {\n    41\n}\n",
+        )
+        .unwrap();
+        fs::write(root.join("old.txt"), "going away\n").unwrap();
+        git(&root, &["init", "-q"]);
+        git(&root, &["add", "."]);
+        git(&root, &["commit", "-q", "-m", "initial"]);
+
+        fs::remove_file(root.join("old.txt")).unwrap();
+        for (path, content) in TEXT_FILES {
+            fs::write(root.join(path), content).unwrap();
+        }
+        fs::write(root.join("logo.png"), b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR").unwrap();
+        root
+    }
+
+    /// Bundle the sample repository in `format`, with every optional section,
+    /// and parse it back
+    fn round_trip(name: &str, format: Format) {
+        let root = sample_repo(name);
+        let bundle = Bundler::new(&root)
+            .include(["*"])
+            .format(format)
+            .tree(true)
+            .toc(true)
+            .with_diff("HEAD")
+            .collect()
+            .unwrap();
+        let mut out = Vec::new();
+        bundle.write(&mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        match format {
+            Format::Markdown => {
+                assert!(text.contains("## Directory tree"), "{}", text);
+                assert!(text.contains("````markdown"), "{}", text);
+                assert!(text.contains(BINARY_PREFIX), "{}", text);
+            }
+            Format::Xml => {
+                assert!(text.contains("<directory_tree>"), "{}", text);
+                assert!(text.contains(BINARY_PREFIX), "{}", text);
+            }
+            _ => assert!(text.contains("\"binary\":\"PNG image\""), "{}", text),
+        }
+        assert!(
+            text.contains("-going away"),
+            "pending changes missing:\n{}",
+            text
+        );
+
+        let mut files: Vec<(String, Vec<u8>)> = parse_bundle(&text)
+            .unwrap()
+            .into_iter()
+            .map(|f| (f.path, f.content))
+            .collect();
+        files.sort();
+        let expected: Vec<(String, Vec<u8>)> = TEXT_FILES
+            .iter()
+            .map(|(path, content)| (path.to_string(), content.to_vec()))
+            .collect();
+        assert_eq!(files, expected, "bundle:\n{}", text);
+
+        // Unpacking over the tree the bundle came from changes nothing
+        let parsed = parse_bundle(&text).unwrap();
+        let options = UnpackOptions {
+            dry_run: true,
+            diff: false,
+        };
+        let summary = unpack(&parsed, &root, &options).unwrap();
+        assert_eq!(summary.unchanged, TEXT_FILES.len());
+        assert_eq!(summary.created + summary.updated + summary.rejected, 0);
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn markdown_round_trip() {
+        round_trip("markdown", Format::Markdown);
+    }
+
+    #[test]
+    fn xml_round_trip() {
+        round_trip("xml", Format::Xml);
+    }
+
+    #[test]
+    fn json_round_trip() {
+        round_trip("json", Format::Json);
+    }
+
+    #[test]
+    fn jsonl_round_trip() {
+        round_trip("jsonl", Format::JsonLines);
+    }
+
+    #[test]
+    fn safe_join_rejects_parent_components() {
+        let target = Path::new("/srv/out");
+        assert!(safe_join(target, "../escape.txt").is_err());
+        assert!(safe_join(target, "src/../../escape.txt").is_err());
+        assert!(safe_join(target, "src/..").is_err());
+    }
+
+    #[test]
+    fn safe_join_rejects_absolute_and_empty_paths() {
+        let target = Path::new("/srv/out");
+        assert!(safe_join(target, "/etc/passwd").is_err());
+        assert!(safe_join(target, "").is_err());
+        assert!(safe_join(target, ".").is_err());
+    }
+
+    #[test]
+    fn safe_join_accepts_relative_paths() {
+        let target = Path::new("/srv/out");
+        assert_eq!(
+            safe_join(target, "./src/main.rs").unwrap(),
+            Path::new("/srv/out/src/main.rs")
+        );
+    }
+
+    #[test]
+    fn unpack_rejects_traversal_without_writing() {
+        let root = temp_dir("traversal");
+        let target = root.join("target");
+        let files = [
+            BundleFile {
+                path: "../escape.txt".to_string(),
+                content: b"x".to_vec(),
+            },
+            BundleFile {
+                path: root.join("absolute.txt").display().to_string(),
+                content: b"x".to_vec(),
+            },
+        ];
+        let options = UnpackOptions {
+            dry_run: false,
+            diff: false,
+        };
+        let summary = unpack(&files, &target, &options).unwrap();
+        assert_eq!(summary.rejected, 2);
+        assert!(!root.join("escape.txt").exists());
+        assert!(!root.join("absolute.txt").exists());
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unpack_refuses_to_write_through_symlinks() {
+        use std::os::unix::fs::symlink;
+
+        let root = temp_dir("symlink");
+        let target = root.join("target");
+        let outside = root.join("outside");
+        fs::create_dir_all(&target).unwrap();
+        fs::create_dir_all(&outside).unwrap();
+        fs::write(outside.join("victim.txt"), "original").unwrap();
+        symlink(&outside, target.join("linked_dir")).unwrap();
+        symlink(outside.join("victim.txt"), target.join("linked_file")).unwrap();
+
+        let dest = target.join("linked_dir/new.txt");
+        assert!(check_resolved(&target, &dest, "linked_dir/new.txt").is_err());
+        let dest = target.join("linked_file");
+        assert!(check_resolved(&target, &dest, "linked_file").is_err());
+
+        let files = [
+            BundleFile {
+                path: "linked_dir/new.txt".to_string(),
+                content: b"x".to_vec(),
+            },
+            BundleFile {
+                path: "linked_file".to_string(),
+                content: b"x".to_vec(),
+            },
+        ];
+        let options = UnpackOptions {
+            dry_run: false,
+            diff: false,
+        };
+        let summary = unpack(&files, &target, &options).unwrap();
+        assert_eq!(summary.rejected, 2);
+        assert!(!outside.join("new.txt").exists());
+        assert_eq!(
+            fs::read_to_string(outside.join("victim.txt")).unwrap(),
+            "original"
+        );
+        let _ = fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,177 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::time::Duration;
+
+use dircat::Bundle;
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+/// How long the tree has to be quiet before the bundle is rebuilt
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Files that change what gets selected even though they don't match the patterns
+const SELECTION_FILES: &[&str] = &[".gitignore", ".ignore", ".dircatignore"];
+
+/// Watches a base directory for changes that affect a bundle
+pub struct ChangeWatcher {
+    // Dropping the watcher stops the notifications
+    _watcher: RecommendedWatcher,
+    events: Receiver<notify::Result<Event>>,
+    /// Canonical base directory, which event paths are relative to
+    base: PathBuf,
+    /// Files the bundle is written to, whose changes are our own
+    outputs: Vec<PathBuf>,
+    /// Canonical git directory, watched when the selection depends on git state
+    git_dir: Option<PathBuf>,
+}
+
+/// The git directory of the repository holding `dir`, following the `.git`
+/// file of a worktree or submodule
+fn find_git_dir(dir: &Path) -> Option<PathBuf> {
+    for ancestor in dir.ancestors() {
+        let dot_git = ancestor.join(".git");
+        if dot_git.is_dir() {
+            return dot_git.canonicalize().ok();
+        }
+        if dot_git.is_file() {
+            let text = fs::read_to_string(&dot_git).ok()?;
+            let target = text.strip_prefix("gitdir:")?.trim();
+            return ancestor.join(target).canonicalize().ok();
+        }
+    }
+    None
+}
+
+/// Whether a path inside the git directory records what is staged or committed
+fn is_git_state(rel_path: &Path) -> bool {
+    rel_path == Path::new("index")
+        || rel_path == Path::new("HEAD")
+        || rel_path == Path::new("packed-refs")
+        || rel_path.starts_with("refs")
+}
+
+/// Absolute form of a path that may not exist yet, resolved through its parent
+fn absolute(path: &Path) -> PathBuf {
+    let parent = match path.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p,
+        _ => Path::new("."),
+    };
+    match (parent.canonicalize(), path.file_name()) {
+        (Ok(dir), Some(name)) => dir.join(name),
+        _ => path.to_path_buf(),
+    }
+}
+
+impl ChangeWatcher {
+    /// Start watching `base_dir` recursively, ignoring writes to `outputs`
+    ///
+    /// With `watch_git`, staging, commits and branch switches count as changes
+    /// too, for bundles selected by git state or carrying pending diffs.
+    pub fn new(
+        base_dir: &Path,
+        outputs: &[PathBuf],
+        watch_git: bool,
+    ) -> Result<ChangeWatcher, String> {
+        let base = base_dir
+            .canonicalize()
+            .map_err(|e| format!("{}: {}", base_dir.display(), e))?;
+        let (tx, events) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(tx).map_err(|e| e.to_string())?;
+        watcher
+            .watch(&base, RecursiveMode::Recursive)
+            .map_err(|e| format!("watching {}: {}", base.display(), e))?;
+
+        let git_dir = if watch_git { find_git_dir(&base) } else { None };
+        // A repository root above the base directory isn't covered by its watch
+        if let Some(git_dir) = git_dir.as_ref().filter(|d| !d.starts_with(&base)) {
+            watcher
+                .watch(git_dir, RecursiveMode::Recursive)
+                .map_err(|e| format!("watching {}: {}", git_dir.display(), e))?;
+        }
+
+        Ok(ChangeWatcher {
+            _watcher: watcher,
+            events,
+            base,
+            outputs: outputs.iter().map(|p| absolute(p)).collect(),
+            git_dir,
+        })
+    }
+
+    /// Whether a change to `path` could alter `bundle`
+    ///
+    /// With no bundle (the last run failed), any change is worth a retry.
+    fn is_relevant(&self, path: &Path, bundle: Option<&Bundle>) -> bool {
+        if self.outputs.iter().any(|o| o == path) {
+            return false;
+        }
+        if let Some(git_dir) = &self.git_dir
+            && let Ok(rel_path) = path.strip_prefix(git_dir)
+        {
+            return is_git_state(rel_path);
+        }
+        let Ok(rel_path) = path.strip_prefix(&self.base) else {
+            return false;
+        };
+        let Some(bundle) = bundle else {
+            return true;
+        };
+
+        let name = rel_path.file_name().unwrap_or_default();
+        SELECTION_FILES.iter().any(|f| name == *f)
+            || bundle.matches_patterns(rel_path)
+            // A bundled file, or a directory holding some, was removed or renamed
+            || bundle
+                .files()
+                .iter()
+                .any(|f| f.rel_path.starts_with(rel_path))
+    }
+
+    /// A changed path as it is reported: relative to the base directory, or
+    /// under `.git/` for the repository's state
+    fn display_path(&self, path: &Path) -> Option<PathBuf> {
+        if let Some(git_dir) = &self.git_dir
+            && let Ok(rel_path) = path.strip_prefix(git_dir)
+        {
+            return Some(Path::new(".git").join(rel_path));
+        }
+        path.strip_prefix(&self.base).ok().map(Path::to_path_buf)
+    }
+
+    /// Block until a relevant change has settled, returning the changed paths
+    pub fn wait(&self, bundle: Option<&Bundle>) -> Result<Vec<PathBuf>, String> {
+        let mut changed = Vec::new();
+        loop {
+            let event = if changed.is_empty() {
+                self.events.recv().map_err(|e| e.to_string())?
+            } else {
+                match self.events.recv_timeout(DEBOUNCE) {
+                    Ok(event) => event,
+                    Err(RecvTimeoutError::Timeout) => break,
+                    Err(e) => return Err(e.to_string()),
+                }
+            };
+            let event = match event {
+                Ok(event) => event,
+                Err(e) => {
+                    eprintln!("Watch error: {}", e);
+                    continue;
+                }
+            };
+            // Reading files for the bundle shows up as access events
+            if matches!(event.kind, EventKind::Access(_)) {
+                continue;
+            }
+            for path in event.paths {
+                if self.is_relevant(&path, bundle)
+                    && let Some(rel_path) = self.display_path(&path)
+                    && !changed.contains(&rel_path)
+                {
+                    changed.push(rel_path);
+                }
+            }
+        }
+        changed.sort();
+        Ok(changed)
+    }
+}
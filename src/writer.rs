@@ -0,0 +1,39 @@
+use std::io;
+
+use crate::content::FileContent;
+use crate::{Bundle, SourceFile};
+
+/// Renders a bundle one piece at a time
+///
+/// [`Bundle::write_to`] calls `begin` once, then `file` or `read_error` for
+/// each file in sorted order, then `end`. Every file is read once, however many
+/// writers share the run. Implementations should flush in `end`.
+pub trait BundleWriter {
+    /// Write whatever precedes the files, such as a header or table of contents
+    fn begin(&mut self, _bundle: &Bundle) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Write the section for `file`, the `index`th file of the bundle
+    fn file(
+        &mut self,
+        bundle: &Bundle,
+        index: usize,
+        file: &SourceFile,
+        content: &FileContent,
+    ) -> io::Result<()>;
+
+    /// Write the section for a file that couldn't be read
+    fn read_error(
+        &mut self,
+        bundle: &Bundle,
+        index: usize,
+        file: &SourceFile,
+        error: &io::Error,
+    ) -> io::Result<()>;
+
+    /// Write whatever follows the files, such as pending changes
+    fn end(&mut self, _bundle: &Bundle) -> io::Result<()> {
+        Ok(())
+    }
+}
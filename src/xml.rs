@@ -0,0 +1,182 @@
+use crate::content::{Content, FileContent};
+use crate::git::{FileDiff, PendingChanges};
+use crate::writer::BundleWriter;
+use crate::{BUNDLE_MARKER, Bundle, SourceFile};
+use std::io::{self, Write};
+
+/// Escape text for use in XML character data or attribute values
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            c if is_xml_char(c) => out.push(c),
+            _ => out.push(char::REPLACEMENT_CHARACTER),
+        }
+    }
+    out
+}
+
+/// Wrap text in a CDATA section, splitting any `]]>` so it can't close the section early
+fn cdata(text: &str) -> String {
+    let sanitized: String = text
+        .chars()
+        .map(|c| {
+            if is_xml_char(c) {
+                c
+            } else {
+                char::REPLACEMENT_CHARACTER
+            }
+        })
+        .collect();
+    format!(
+        "<![CDATA[{}]]>",
+        sanitized.replace("]]>", "]]]]><![CDATA[>")
+    )
+}
+
+/// Characters allowed in an XML 1.0 document
+fn is_xml_char(c: char) -> bool {
+    matches!(c, '\t' | '\n' | '\r' | '\u{20}'..='\u{D7FF}' | '\u{E000}'..='\u{FFFD}' | '\u{10000}'..)
+}
+
+/// Write one file's `<diff>` element inside `<pending_changes>`
+pub(crate) fn write_diff<W: Write>(writer: &mut W, diff: &FileDiff) -> io::Result<()> {
+    writeln!(
+        writer,
+        "<diff source=\"{}\">",
+        escape(&diff.rel_path.display().to_string())
+    )?;
+    writeln!(writer, "{}", cdata(&diff.patch))?;
+    writeln!(writer, "</diff>")
+}
+
+/// Text that follows the documents: the `<pending_changes>` element's tags
+/// around the diffs, if there are any, and the closing `</documents>`
+pub(crate) fn closing_tags(changes: Option<&PendingChanges>) -> String {
+    match changes {
+        Some(changes) => format!(
+            "<pending_changes base=\"{}\">\n</pending_changes>\n</documents>\n",
+            escape(&changes.base)
+        ),
+        None => "</documents>\n".to_string(),
+    }
+}
+
+/// Writes `<documents>` XML, the layout Anthropic recommends for long-context prompts
+pub struct XmlWriter<W> {
+    writer: W,
+}
+
+impl<W: Write> XmlWriter<W> {
+    pub fn new(writer: W) -> XmlWriter<W> {
+        XmlWriter { writer }
+    }
+
+    fn document_start(&mut self, index: usize, file: &SourceFile) -> io::Result<()> {
+        writeln!(self.writer, "<document index=\"{}\">", index + 1)?;
+        writeln!(
+            self.writer,
+            "<source>{}</source>",
+            escape(&file.rel_path.display().to_string())
+        )
+    }
+
+    fn document_end(&mut self) -> io::Result<()> {
+        writeln!(self.writer, "</document_content>")?;
+        writeln!(self.writer, "</document>")
+    }
+}
+
+impl<W: Write> BundleWriter for XmlWriter<W> {
+    fn begin(&mut self, bundle: &Bundle) -> io::Result<()> {
+        let writer = &mut self.writer;
+        writeln!(writer, "{}", BUNDLE_MARKER)?;
+        writeln!(writer, "<documents>")?;
+
+        if let Some(tree) = &bundle.tree {
+            writeln!(writer, "<directory_tree>")?;
+            writeln!(writer, "{}", cdata(tree))?;
+            writeln!(writer, "</directory_tree>")?;
+        }
+        Ok(())
+    }
+
+    fn file(
+        &mut self,
+        _bundle: &Bundle,
+        index: usize,
+        file: &SourceFile,
+        content: &FileContent,
+    ) -> io::Result<()> {
+        self.document_start(index, file)?;
+        let writer = &mut self.writer;
+        match &content.content {
+            Content::Text(text) => {
+                writeln!(writer, "<document_content>")?;
+                writeln!(writer, "{}", cdata(text))?;
+            }
+            Content::Binary {
+                kind,
+                base64: Some(encoded),
+            } => {
+                writeln!(
+                    writer,
+                    "<document_content encoding=\"base64\" type=\"{}\" size=\"{}\" sha256=\"{}\">",
+                    escape(kind),
+                    content.size,
+                    content.sha256
+                )?;
+                writeln!(writer, "{}", encoded)?;
+            }
+            Content::Binary { base64: None, .. } => {
+                writeln!(writer, "<document_content>")?;
+                writeln!(
+                    writer,
+                    "{}",
+                    escape(&content.placeholder().unwrap_or_default())
+                )?;
+            }
+        }
+        self.document_end()
+    }
+
+    fn read_error(
+        &mut self,
+        _bundle: &Bundle,
+        index: usize,
+        file: &SourceFile,
+        error: &io::Error,
+    ) -> io::Result<()> {
+        self.document_start(index, file)?;
+        writeln!(self.writer, "<document_content>")?;
+        writeln!(
+            self.writer,
+            "{}",
+            escape(&format!("[Error reading file: {}]", error))
+        )?;
+        self.document_end()
+    }
+
+    fn end(&mut self, bundle: &Bundle) -> io::Result<()> {
+        let writer = &mut self.writer;
+        if let Some(changes) = bundle.changes.as_ref().filter(|c| !c.diffs.is_empty()) {
+            writeln!(
+                writer,
+                "<pending_changes base=\"{}\">",
+                escape(&changes.base)
+            )?;
+            for diff in &changes.diffs {
+                write_diff(writer, diff)?;
+            }
+            writeln!(writer, "</pending_changes>")?;
+        }
+
+        writeln!(writer, "</documents>")?;
+        writer.flush()
+    }
+}